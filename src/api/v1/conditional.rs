@@ -0,0 +1,269 @@
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::Method;
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// What a download response can be validated against.
+pub struct Validators {
+    /// A strong entity tag, including its quotes.
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// Derives a strong entity tag from the artifact's SHA-256, which is the one digest the
+    /// cache records for every entry.
+    pub fn new(sha256: Option<&str>, last_modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            etag: sha256.map(|digest| format!("\"{}\"", digest.to_ascii_lowercase())),
+            last_modified,
+        }
+    }
+
+    pub fn last_modified_header(&self) -> Option<HttpDate> {
+        self.last_modified
+            .map(|time| HttpDate::from(SystemTime::from(time)))
+    }
+}
+
+/// How to answer a request for an artifact.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    NotModified,
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+/// Evaluates the request's preconditions and `Range` header against the artifact.
+/// Ranges are only honoured on `GET` and when the artifact's `size` is known; anything
+/// beyond a single range is answered with the full body.
+pub fn evaluate(req: &HttpRequest, validators: &Validators, size: Option<u64>) -> Outcome {
+    if not_modified(req, validators) {
+        return Outcome::NotModified;
+    }
+
+    let Some(size) = size else {
+        return Outcome::Full;
+    };
+    if req.method() != Method::GET || !if_range_matches(req, validators) {
+        return Outcome::Full;
+    }
+
+    match header_str(req, header::RANGE).and_then(parse_range) {
+        Some(spec) => spec.resolve(size),
+        None => Outcome::Full,
+    }
+}
+
+fn header_str(req: &HttpRequest, name: header::HeaderName) -> Option<&str> {
+    req.headers().get(name)?.to_str().ok()
+}
+
+/// `If-None-Match` takes precedence over `If-Modified-Since`, which is only consulted when
+/// the former is absent.
+fn not_modified(req: &HttpRequest, validators: &Validators) -> bool {
+    if let Some(value) = header_str(req, header::IF_NONE_MATCH) {
+        return value.trim() == "*"
+            || validators.etag.as_deref().is_some_and(|etag| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim().trim_start_matches("W/") == etag)
+            });
+    }
+
+    match (
+        header_str(req, header::IF_MODIFIED_SINCE).and_then(|v| HttpDate::from_str(v).ok()),
+        validators.last_modified,
+    ) {
+        (Some(since), Some(modified)) => modified.timestamp() <= to_timestamp(since),
+        _ => false,
+    }
+}
+
+/// A range is only served if `If-Range` is absent or still names the current artifact,
+/// either by a strong entity tag or by its exact modification date.
+fn if_range_matches(req: &HttpRequest, validators: &Validators) -> bool {
+    let Some(value) = header_str(req, header::IF_RANGE).map(str::trim) else {
+        return true;
+    };
+
+    if value.starts_with('"') || value.starts_with("W/") {
+        return validators.etag.as_deref() == Some(value);
+    }
+
+    match (HttpDate::from_str(value), validators.last_modified) {
+        (Ok(date), Some(modified)) => modified.timestamp() == to_timestamp(date),
+        _ => false,
+    }
+}
+
+fn to_timestamp(date: HttpDate) -> i64 {
+    DateTime::<Utc>::from(SystemTime::from(date)).timestamp()
+}
+
+enum RangeSpec {
+    /// `first-` or `first-last`.
+    From(u64, Option<u64>),
+    /// `-length`, the last `length` bytes.
+    Suffix(u64),
+}
+
+impl RangeSpec {
+    /// No range of an empty artifact can be satisfied.
+    fn resolve(self, size: u64) -> Outcome {
+        match self {
+            _ if size == 0 => Outcome::Unsatisfiable,
+            RangeSpec::From(first, _) if first >= size => Outcome::Unsatisfiable,
+            RangeSpec::From(first, last) => {
+                let end = last.map_or(size, |last| last.saturating_add(1).min(size));
+                Outcome::Partial(first..end)
+            }
+            RangeSpec::Suffix(0) => Outcome::Unsatisfiable,
+            RangeSpec::Suffix(length) => Outcome::Partial(size.saturating_sub(length)..size),
+        }
+    }
+}
+
+/// Parses a single `bytes` range. Malformed headers and multiple ranges yield `None`, so
+/// that they are ignored as the spec allows.
+fn parse_range(value: &str) -> Option<RangeSpec> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (first, last) = spec.trim().split_once('-')?;
+    if first.is_empty() {
+        return Some(RangeSpec::Suffix(last.parse().ok()?));
+    }
+
+    let first = first.parse().ok()?;
+    let last = match last {
+        "" => None,
+        last => Some(last.parse().ok()?),
+    };
+    if last.is_some_and(|last| last < first) {
+        return None;
+    }
+    Some(RangeSpec::From(first, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use chrono::TimeZone;
+
+    const ETAG: &str = "\"abc\"";
+
+    fn validators() -> Validators {
+        Validators {
+            etag: Some(ETAG.to_string()),
+            last_modified: Some(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()),
+        }
+    }
+
+    fn evaluate_with(headers: &[(header::HeaderName, &str)], size: u64) -> Outcome {
+        let mut req = TestRequest::get();
+        for (name, value) in headers {
+            req = req.insert_header((name.clone(), *value));
+        }
+        evaluate(&req.to_http_request(), &validators(), Some(size))
+    }
+
+    fn range(value: &str, size: u64) -> Outcome {
+        evaluate_with(&[(header::RANGE, value)], size)
+    }
+
+    #[test]
+    fn resolves_single_ranges() {
+        assert_eq!(range("bytes=0-99", 1000), Outcome::Partial(0..100));
+        assert_eq!(range("bytes=900-2000", 1000), Outcome::Partial(900..1000));
+        assert_eq!(range("bytes=100-", 1000), Outcome::Partial(100..1000));
+        assert_eq!(range("bytes=-300", 1000), Outcome::Partial(700..1000));
+        assert_eq!(range("bytes=-3000", 1000), Outcome::Partial(0..1000));
+    }
+
+    #[test]
+    fn rejects_ranges_outside_the_artifact() {
+        assert_eq!(range("bytes=1000-", 1000), Outcome::Unsatisfiable);
+        assert_eq!(range("bytes=-0", 1000), Outcome::Unsatisfiable);
+        assert_eq!(range("bytes=-5", 0), Outcome::Unsatisfiable);
+        assert_eq!(range("bytes=0-", 0), Outcome::Unsatisfiable);
+    }
+
+    #[test]
+    fn ignores_multiple_and_malformed_ranges() {
+        assert_eq!(range("bytes=0-1,5-6", 1000), Outcome::Full);
+        assert_eq!(range("bytes=5-1", 1000), Outcome::Full);
+        assert_eq!(range("items=0-1", 1000), Outcome::Full);
+        assert_eq!(range("bytes=a-b", 1000), Outcome::Full);
+    }
+
+    #[test]
+    fn ignores_ranges_without_a_known_size_or_on_head() {
+        let req = TestRequest::get()
+            .insert_header((header::RANGE, "bytes=0-9"))
+            .to_http_request();
+        assert_eq!(evaluate(&req, &validators(), None), Outcome::Full);
+
+        let req = TestRequest::default()
+            .method(Method::HEAD)
+            .insert_header((header::RANGE, "bytes=0-9"))
+            .to_http_request();
+        assert_eq!(evaluate(&req, &validators(), Some(1000)), Outcome::Full);
+    }
+
+    #[test]
+    fn honours_if_range_only_for_the_current_artifact() {
+        let partial = Outcome::Partial(0..10);
+        let with_if_range = |value| {
+            evaluate_with(
+                &[(header::RANGE, "bytes=0-9"), (header::IF_RANGE, value)],
+                1000,
+            )
+        };
+
+        assert_eq!(with_if_range(ETAG), partial);
+        assert_eq!(with_if_range("\"other\""), Outcome::Full);
+        // Weak tags never match under the strong comparison If-Range requires.
+        assert_eq!(with_if_range("W/\"abc\""), Outcome::Full);
+        assert_eq!(with_if_range("Wed, 01 May 2024 12:00:00 GMT"), partial);
+        assert_eq!(
+            with_if_range("Wed, 01 May 2024 12:00:01 GMT"),
+            Outcome::Full
+        );
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let later = "Thu, 02 May 2024 00:00:00 GMT";
+
+        assert_eq!(
+            evaluate_with(&[(header::IF_MODIFIED_SINCE, later)], 1000),
+            Outcome::NotModified
+        );
+        assert_eq!(
+            evaluate_with(
+                &[
+                    (header::IF_NONE_MATCH, "\"other\""),
+                    (header::IF_MODIFIED_SINCE, later)
+                ],
+                1000
+            ),
+            Outcome::Full
+        );
+        assert_eq!(
+            evaluate_with(&[(header::IF_NONE_MATCH, "\"x\", W/\"abc\"")], 1000),
+            Outcome::NotModified
+        );
+        assert_eq!(
+            evaluate_with(&[(header::IF_NONE_MATCH, "*")], 1000),
+            Outcome::NotModified
+        );
+    }
+}
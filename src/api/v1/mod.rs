@@ -1,4 +1,6 @@
+mod conditional;
 pub mod models;
+mod pagination;
 pub mod routes;
 
 use actix_web::web;
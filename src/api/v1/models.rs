@@ -1,4 +1,6 @@
+use crate::error::WarehouseError;
 use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
@@ -7,6 +9,15 @@ pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    /// Stable identifier of the kind of error, when `success` is false.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    /// For list endpoints, how many items matched the filters across all pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    /// For list endpoints, the `cursor` that fetches the next page, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -22,13 +33,39 @@ pub struct VersionInfo {
     #[serde(rename = "type")]
     pub version_type: String,
     pub is_stable: bool,
+    pub release_time: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct BuildInfo {
     pub id: String,
     pub version: VersionInfo,
+    pub is_stable: bool,
     pub download_url: Option<String>,
+    /// `server` for a runnable jar, `installer` when the installer must be run first. An
+    /// installer may install several builds, as Quilt's does; the build `id` must then be
+    /// passed to it as the loader version.
+    pub artifact: String,
+    pub checksums: ChecksumInfo,
+    pub release_time: Option<DateTime<Utc>>,
+    /// The major Java version needed to run the build, when the loader publishes it.
+    pub java_version: Option<u32>,
+    /// Size of the download in bytes, when the loader publishes it.
+    pub size: Option<u64>,
+    pub changes: Vec<ChangeInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ChangeInfo {
+    pub commit: Option<String>,
+    pub summary: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ChecksumInfo {
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+    pub md5: Option<String>,
 }
 
 #[derive(Deserialize, ToSchema, IntoParams)]
@@ -37,6 +74,39 @@ pub struct VersionQuery {
     pub loader: String,
     #[serde(default)]
     pub stable_only: bool,
+    pub range: Option<String>,
+    /// Page size; all matching items are returned when omitted.
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    pub sort: Option<SortOrder>,
+    /// Comma-separated version types to include, e.g. `release,snapshot`.
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+    /// Only items released at or after this RFC 3339 timestamp or `YYYY-MM-DD` date. Items
+    /// without a release time are left out, and loaders that publish none reject it.
+    pub since: Option<String>,
+}
+
+/// Order of list endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    /// Newest first.
+    #[default]
+    Desc,
+}
+
+/// How `/download` answers once it has resolved a build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadMode {
+    /// Serve the file through the cache.
+    #[default]
+    Proxy,
+    /// Redirect to the upstream download URL without touching the cache.
+    Redirect,
 }
 
 #[derive(Deserialize, ToSchema, IntoParams)]
@@ -45,6 +115,9 @@ pub struct DownloadQuery {
     pub loader: String,
     pub version: String,
     pub build_id: Option<String>,
+    pub installer: Option<String>,
+    pub mode: Option<DownloadMode>,
+    pub range: Option<String>,
 }
 
 #[derive(Deserialize, ToSchema, IntoParams)]
@@ -52,11 +125,27 @@ pub struct BuildQuery {
     pub game: String,
     pub loader: String,
     pub version: String,
+    #[serde(default)]
+    pub stable_only: bool,
+    /// Page size; all matching items are returned when omitted.
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    pub sort: Option<SortOrder>,
+    /// Comma-separated version types to include, e.g. `release,snapshot`.
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+    /// Only items released at or after this RFC 3339 timestamp or `YYYY-MM-DD` date. Items
+    /// without a release time are left out, and loaders that publish none reject it.
+    pub since: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct ErrorResponse {
+    pub success: bool,
     pub error: String,
+    /// Stable identifier of the kind of error, e.g. `version_not_found` or `upstream_timeout`.
+    pub code: String,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -71,18 +160,33 @@ impl<T: Serialize> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
+            total: None,
+            next_cursor: None,
         }
     }
 
-    pub fn error(msg: impl Into<String>) -> ApiResponse<T> {
+    pub fn page(data: T, total: usize, next_cursor: Option<String>) -> Self {
+        Self {
+            total: Some(total),
+            next_cursor,
+            ..Self::success(data)
+        }
+    }
+
+    pub fn error(error: &WarehouseError) -> ApiResponse<T> {
         Self {
             success: false,
             data: None,
-            error: Some(msg.into()),
+            error: Some(error.to_string()),
+            code: Some(error.code()),
+            total: None,
+            next_cursor: None,
         }
     }
 
-    pub fn error_response(msg: impl Into<String>) -> HttpResponse {
-        HttpResponse::NotFound().json(Self::error(msg))
+    pub fn error_response(error: impl Into<WarehouseError>) -> HttpResponse {
+        let error = error.into();
+        HttpResponse::build(error.status()).json(Self::error(&error))
     }
-}
\ No newline at end of file
+}
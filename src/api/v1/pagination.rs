@@ -0,0 +1,232 @@
+use crate::api::v1::models::SortOrder;
+use crate::error::WarehouseError;
+use crate::game::VersionType;
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// The largest page a client may ask for.
+const MAX_LIMIT: usize = 1000;
+
+/// Filtering, ordering and paging of a list endpoint, parsed from its query.
+pub struct ListOptions {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    sort: SortOrder,
+    types: Option<Vec<VersionType>>,
+    since: Option<DateTime<Utc>>,
+}
+
+/// One page of a list, with the number of items across all pages.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub next_cursor: Option<String>,
+}
+
+impl ListOptions {
+    pub fn parse(
+        limit: Option<usize>,
+        cursor: Option<&str>,
+        sort: Option<SortOrder>,
+        types: Option<&str>,
+        since: Option<&str>,
+    ) -> Result<Self, WarehouseError> {
+        if limit.is_some_and(|limit| limit == 0 || limit > MAX_LIMIT) {
+            return Err(WarehouseError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                MAX_LIMIT
+            )));
+        }
+
+        let types = types.map(|types| {
+            types
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(VersionType::from)
+                .collect()
+        });
+
+        Ok(Self {
+            limit,
+            cursor: cursor.map(String::from),
+            sort: sort.unwrap_or_default(),
+            types,
+            since: since.map(parse_since).transpose()?,
+        })
+    }
+
+    /// Whether an item passes the `type` and `since` filters. Items without a release time
+    /// never pass `since`.
+    pub fn includes(
+        &self,
+        version_type: &VersionType,
+        release_time: Option<DateTime<Utc>>,
+    ) -> bool {
+        let type_matches = self
+            .types
+            .as_ref()
+            .is_none_or(|types| types.contains(version_type));
+        let time_matches = self
+            .since
+            .is_none_or(|since| release_time.is_some_and(|time| time >= since));
+        type_matches && time_matches
+    }
+
+    /// Rejects `since` for a list in which no item has a release time, as loaders that do not
+    /// publish release times would otherwise answer every such query with an empty page.
+    pub fn check_since<T>(
+        &self,
+        items: &[T],
+        release_time: impl Fn(&T) -> Option<DateTime<Utc>>,
+    ) -> Result<(), WarehouseError> {
+        if self.since.is_some()
+            && !items.is_empty()
+            && !items.iter().any(|item| release_time(item).is_some())
+        {
+            return Err(WarehouseError::BadRequest(
+                "since is not supported here, as this loader publishes no release times"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Orders `items`, which must be newest first, and cuts the page that follows the
+    /// item named by the cursor. Cursors are item IDs, so pages stay consistent when newer
+    /// items are published in between requests.
+    pub fn paginate<T>(
+        &self,
+        mut items: Vec<T>,
+        id: impl Fn(&T) -> &str,
+    ) -> Result<Page<T>, WarehouseError> {
+        if self.sort == SortOrder::Asc {
+            items.reverse();
+        }
+        let total = items.len();
+
+        let start = match &self.cursor {
+            Some(cursor) => {
+                items
+                    .iter()
+                    .position(|item| id(item) == cursor)
+                    .ok_or_else(|| {
+                        WarehouseError::BadRequest(format!(
+                            "cursor '{}' does not name a listed item",
+                            cursor
+                        ))
+                    })?
+                    + 1
+            }
+            None => 0,
+        };
+        let end = self.limit.map_or(total, |limit| (start + limit).min(total));
+        let next_cursor = (end < total).then(|| id(&items[end - 1]).to_string());
+
+        Ok(Page {
+            items: items.drain(start..end).collect(),
+            total,
+            next_cursor,
+        })
+    }
+}
+
+fn parse_since(value: &str) -> Result<DateTime<Utc>, WarehouseError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.to_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .map_err(|_| {
+            WarehouseError::BadRequest(format!(
+                "since '{}' is neither an RFC 3339 timestamp nor a date",
+                value
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDS: [&str; 5] = ["5", "4", "3", "2", "1"];
+
+    fn options(limit: Option<usize>, cursor: Option<&str>, sort: SortOrder) -> ListOptions {
+        ListOptions::parse(limit, cursor, Some(sort), None, None).unwrap()
+    }
+
+    fn page(options: &ListOptions) -> Page<&'static str> {
+        options.paginate(IDS.to_vec(), |id| id).unwrap()
+    }
+
+    #[test]
+    fn pages_follow_the_cursor() {
+        let first = page(&options(Some(2), None, SortOrder::Desc));
+        assert_eq!(first.items, ["5", "4"]);
+        assert_eq!(first.total, 5);
+        assert_eq!(first.next_cursor.as_deref(), Some("4"));
+
+        let second = page(&options(Some(2), Some("4"), SortOrder::Desc));
+        assert_eq!(second.items, ["3", "2"]);
+        assert_eq!(second.next_cursor.as_deref(), Some("2"));
+
+        let last = page(&options(Some(2), Some("2"), SortOrder::Desc));
+        assert_eq!(last.items, ["1"]);
+        assert_eq!(last.total, 5);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn ascending_pages_start_from_the_oldest_item() {
+        let first = page(&options(Some(3), None, SortOrder::Asc));
+        assert_eq!(first.items, ["1", "2", "3"]);
+        assert_eq!(first.next_cursor.as_deref(), Some("3"));
+
+        let last = page(&options(Some(3), Some("3"), SortOrder::Asc));
+        assert_eq!(last.items, ["4", "5"]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn a_page_that_ends_the_list_has_no_cursor() {
+        assert_eq!(
+            page(&options(None, None, SortOrder::Desc)).next_cursor,
+            None
+        );
+        assert_eq!(
+            page(&options(Some(5), None, SortOrder::Desc)).next_cursor,
+            None
+        );
+
+        let empty = page(&options(Some(2), Some("1"), SortOrder::Desc));
+        assert!(empty.items.is_empty());
+        assert_eq!(empty.next_cursor, None);
+    }
+
+    #[test]
+    fn rejects_unknown_cursors_and_limits() {
+        let options = options(Some(2), Some("6"), SortOrder::Desc);
+        assert!(options.paginate(IDS.to_vec(), |id| id).is_err());
+
+        for limit in [0, MAX_LIMIT + 1] {
+            assert!(ListOptions::parse(Some(limit), None, None, None, None).is_err());
+        }
+    }
+
+    #[test]
+    fn since_needs_release_times() {
+        let options = ListOptions::parse(None, None, None, None, Some("2024-05-01")).unwrap();
+        let released = "2024-05-02T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        assert!(options.includes(&VersionType::Release, Some(released)));
+        assert!(!options.includes(&VersionType::Release, None));
+        assert!(options.check_since(&[Some(released), None], |t| *t).is_ok());
+        assert!(options
+            .check_since(&[None::<DateTime<Utc>>], |t| *t)
+            .is_err());
+        assert!(options
+            .check_since(&[] as &[Option<DateTime<Utc>>], |t| *t)
+            .is_ok());
+
+        assert!(ListOptions::parse(None, None, None, None, Some("May 1st")).is_err());
+    }
+}
@@ -1,6 +1,24 @@
+use crate::api::v1::conditional::{self, Outcome, Validators};
 use crate::api::v1::models::*;
-use crate::{game::Version, AppState};
-use actix_web::{get, web, HttpResponse, Responder};
+use crate::api::v1::pagination::ListOptions;
+use crate::error::WarehouseError;
+use crate::metrics::metrics;
+use crate::storage::ByteStream;
+use crate::{
+    game::{compare_build_ids, Build, BuildOptions, BuildSelector, Download, VersionSelector},
+    version_order::VersionRange,
+    AppState,
+};
+use actix_web::http::header::{
+    self, ContentDisposition, ContentRange, ContentRangeSpec, DispositionParam, DispositionType,
+    LastModified,
+};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{get, route, web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use futures_util::{stream, TryStreamExt};
+use std::str::FromStr;
+
+const JAVA_ARCHIVE: &str = "application/java-archive";
 
 
 #[utoipa::path(
@@ -10,11 +28,20 @@ use actix_web::{get, web, HttpResponse, Responder};
     params(
         ("game" = String, Query, description = "Game identifier"),
         ("loader" = String, Query, description = "Loader identifier"),
-        ("stable_only" = bool, Query, description = "Only show stable versions")
+        ("stable_only" = bool, Query, description = "Only show stable versions"),
+        ("range" = Option<String>, Query, description = "Only show versions in this range, e.g. `>=1.20 <1.21`, `1.20.x` or `~1.19`"),
+        ("limit" = Option<usize>, Query, description = "Page size, up to 1000 (defaults to every matching item)"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("sort" = Option<SortOrder>, Query, description = "`desc` for newest first (default) or `asc`"),
+        ("type" = Option<String>, Query, description = "Comma-separated version types to include, e.g. `release,snapshot`"),
+        ("since" = Option<String>, Query, description = "Only items released at or after this RFC 3339 timestamp or `YYYY-MM-DD` date. Items without a release time are left out, and loaders that publish none reject it")
     ),
     responses(
         (status = 200, description = "List of versions", body = Vec<VersionInfo>),
-        (status = 400, description = "Error response", body = ErrorResponse)
+        (status = 400, description = "Malformed query parameter", body = ErrorResponse),
+        (status = 404, description = "Unknown game, loader, version or build", body = ErrorResponse),
+        (status = 502, description = "Upstream request failed", body = ErrorResponse),
+        (status = 504, description = "Upstream request timed out", body = ErrorResponse)
     )
 )]
 #[get("/versions")]
@@ -23,34 +50,56 @@ async fn list_versions(
     query: web::Query<VersionQuery>,
 ) -> impl Responder {
     let loader = match state.games.get_loader(&query.game, &query.loader).await {
-        Some(loader) => loader,
-        None => {
-            return ApiResponse::<Vec<VersionInfo>>::error_response(format!(
-                "Loader '{}' not found for game '{}'",
-                query.loader, query.game
-            ))
-        }
+        Ok(loader) => loader,
+        Err(e) => return ApiResponse::<Vec<VersionInfo>>::error_response(e),
+    };
+
+    let options = match ListOptions::parse(
+        query.limit,
+        query.cursor.as_deref(),
+        query.sort,
+        query.types.as_deref(),
+        query.since.as_deref(),
+    ) {
+        Ok(options) => options,
+        Err(e) => return ApiResponse::<Vec<VersionInfo>>::error_response(e),
+    };
+
+    let range = match parse_range(query.range.as_deref()) {
+        Ok(range) => range,
+        Err(e) => return ApiResponse::<Vec<VersionInfo>>::error_response(e),
     };
 
     match loader.fetch_versions().await {
         Ok(mut versions) => {
+            if let Err(e) = options.check_since(&versions, |v| v.release_time()) {
+                return ApiResponse::<Vec<VersionInfo>>::error_response(e);
+            }
             if query.stable_only {
                 versions.retain(|v| v.is_stable());
             }
-            let versions = versions
+            if let Some(range) = &range {
+                versions.retain(|v| range.matches(v));
+            }
+            versions.retain(|v| options.includes(v.version_type(), v.release_time()));
+
+            let page = match options.paginate(versions, |v| v.id()) {
+                Ok(page) => page,
+                Err(e) => return ApiResponse::<Vec<VersionInfo>>::error_response(e),
+            };
+            let versions = page
+                .items
                 .into_iter()
                 .map(|v| VersionInfo {
                     id: v.id().to_string(),
                     version_type: v.version_type().to_string(),
                     is_stable: v.is_stable(),
+                    release_time: v.release_time(),
                 })
                 .collect::<Vec<_>>();
-            HttpResponse::Ok().json(ApiResponse::success(versions))
+            HttpResponse::Ok().json(ApiResponse::page(versions, page.total, page.next_cursor))
         }
-        Err(e) => ApiResponse::<Vec<VersionInfo>>::error_response(format!(
-            "Failed to fetch versions: {}",
-            e
-        )),
+        Err(e) => ApiResponse::<Vec<VersionInfo>>::error_response(e),
     }
 }
 
@@ -62,30 +111,75 @@ async fn list_versions(
     params(
         ("game" = String, Query, description = "Game identifier"),
         ("loader" = String, Query, description = "Loader identifier"),
-        ("version" = String, Query, description = "Version identifier")
+        ("version" = String, Query, description = "Version identifier, or `latest`, `latest-stable` or `latest-snapshot`"),
+        ("stable_only" = bool, Query, description = "Only show stable builds"),
+        ("limit" = Option<usize>, Query, description = "Page size, up to 1000 (defaults to every matching item)"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("sort" = Option<SortOrder>, Query, description = "`desc` for newest first (default) or `asc`"),
+        ("type" = Option<String>, Query, description = "Comma-separated version types to include, e.g. `release,snapshot`"),
+        ("since" = Option<String>, Query, description = "Only items released at or after this RFC 3339 timestamp or `YYYY-MM-DD` date. Items without a release time are left out, and loaders that publish none reject it")
     ),
     responses(
         (status = 200, description = "List of builds", body = Vec<BuildInfo>),
-        (status = 400, description = "Error response", body = ErrorResponse)
+        (status = 400, description = "Malformed query parameter", body = ErrorResponse),
+        (status = 404, description = "Unknown game, loader, version or build", body = ErrorResponse),
+        (status = 502, description = "Upstream request failed", body = ErrorResponse),
+        (status = 504, description = "Upstream request timed out", body = ErrorResponse)
     )
 )]
 #[get("/builds")]
 async fn list_builds(state: web::Data<AppState>, query: web::Query<BuildQuery>) -> impl Responder {
     let loader = match state.games.get_loader(&query.game, &query.loader).await {
-        Some(loader) => loader,
-        None => {
-            return ApiResponse::<Vec<BuildInfo>>::error_response(format!(
-                "Loader '{}' not found for game '{}'",
-                query.loader, query.game
+        Ok(loader) => loader,
+        Err(e) => return ApiResponse::<Vec<BuildInfo>>::error_response(e),
+    };
+
+    let options = match ListOptions::parse(
+        query.limit,
+        query.cursor.as_deref(),
+        query.sort,
+        query.types.as_deref(),
+        query.since.as_deref(),
+    ) {
+        Ok(options) => options,
+        Err(e) => return ApiResponse::<Vec<BuildInfo>>::error_response(e),
+    };
+
+    let version = match state
+        .games
+        .resolve_version(
+            loader.as_ref(),
+            &VersionSelector::from(query.version.as_str()),
+            None,
+        )
+        .await
+    {
+        Ok(Some(version)) => version,
+        Ok(None) => {
+            return ApiResponse::<Vec<BuildInfo>>::error_response(WarehouseError::VersionNotFound(
+                query.version.clone(),
             ))
         }
+        Err(e) => return ApiResponse::<Vec<BuildInfo>>::error_response(e),
     };
 
-    let version = Version::new_standard(query.version.clone(), "release".to_string());
-
     match loader.fetch_builds(&version).await {
-        Ok(builds) => {
-            let builds = builds
+        Ok(mut builds) => {
+            if let Err(e) = options.check_since(&builds, |b| b.release_time()) {
+                return ApiResponse::<Vec<BuildInfo>>::error_response(e);
+            }
+            if query.stable_only {
+                builds.retain(|b| b.is_stable());
+            }
+            builds.retain(|b| options.includes(b.version().version_type(), b.release_time()));
+            builds.sort_by(|a, b| compare_build_ids(b.id(), a.id()));
+
+            let page = match options.paginate(builds, |b| b.id()) {
+                Ok(page) => page,
+                Err(e) => return ApiResponse::<Vec<BuildInfo>>::error_response(e),
+            };
+            let builds = page
+                .items
                 .into_iter()
                 .map(|b| BuildInfo {
                     id: b.id().to_string(),
@@ -93,91 +187,312 @@ async fn list_builds(state: web::Data<AppState>, query: web::Query<BuildQuery>)
                         id: b.version().id().to_string(),
                         version_type: b.version().version_type().to_string(),
                         is_stable: b.version().is_stable(),
+                        release_time: b.version().release_time(),
                     },
+                    is_stable: b.is_stable(),
                     download_url: b.download_url().map(String::from),
+                    artifact: b.artifact().to_string(),
+                    checksums: ChecksumInfo {
+                        sha1: b.checksums().sha1.clone(),
+                        sha256: b.checksums().sha256.clone(),
+                        md5: b.checksums().md5.clone(),
+                    },
+                    release_time: b.release_time(),
+                    java_version: b.java_version(),
+                    size: b.size(),
+                    changes: b
+                        .changes()
+                        .iter()
+                        .map(|change| ChangeInfo {
+                            commit: change.commit.clone(),
+                            summary: change.summary.clone(),
+                        })
+                        .collect(),
                 })
                 .collect::<Vec<_>>();
-            HttpResponse::Ok().json(ApiResponse::success(builds))
-        }
-        Err(e) => {
-            ApiResponse::<Vec<BuildInfo>>::error_response(format!("Failed to fetch builds: {}", e))
+            HttpResponse::Ok().json(ApiResponse::page(builds, page.total, page.next_cursor))
         }
+        Err(e) => ApiResponse::<Vec<BuildInfo>>::error_response(e),
     }
 }
 
 
 #[utoipa::path(
-    get,
+    method(get, head),
     path = "/api/v1/download",  
     tag = "warehouse",
     params(
         ("game" = String, Query, description = "Game identifier"),
         ("loader" = String, Query, description = "Loader identifier"),
-        ("version" = String, Query, description = "Version identifier"),
-        ("build_id" = Option<String>, Query, description = "Build identifier")
+        ("version" = String, Query, description = "Version identifier, or `latest`, `latest-stable` or `latest-snapshot`"),
+        ("build_id" = Option<String>, Query, description = "Build identifier, or `latest` or `latest-stable` (defaults to the newest stable build, or the newest build if none is stable)"),
+        ("installer" = Option<String>, Query, description = "Installer version published by the loader, for loaders that need one (defaults to the latest stable)"),
+        ("mode" = Option<DownloadMode>, Query, description = "`proxy` to serve the file, or `redirect` to redirect to the upstream URL (defaults to the server setting)"),
+        ("range" = Option<String>, Query, description = "Version range the version must match, e.g. `~1.19`; with an alias, picks the highest matching version")
     ),
     responses(
         (status = 200, description = "Game server JAR file"),
-        (status = 400, description = "Error response", body = ErrorResponse)
+        (status = 206, description = "Requested byte range of the JAR file"),
+        (status = 304, description = "The JAR file matches `If-None-Match` or `If-Modified-Since`"),
+        (status = 307, description = "Redirect to the cached JAR file in object storage, or to the upstream URL in redirect mode"),
+        (status = 416, description = "The requested byte range is outside the JAR file"),
+        (status = 400, description = "Malformed query parameter", body = ErrorResponse),
+        (status = 404, description = "Unknown game, loader, version or build", body = ErrorResponse),
+        (status = 502, description = "Upstream request failed", body = ErrorResponse),
+        (status = 503, description = "Storage backend unavailable", body = ErrorResponse),
+        (status = 504, description = "Upstream request timed out", body = ErrorResponse)
     )
 )]
-#[get("/download")]
+#[route("/download", method = "GET", method = "HEAD")]
 async fn download_version(
+    req: HttpRequest,
     state: web::Data<AppState>,
     query: web::Query<DownloadQuery>,
 ) -> impl Responder {
     let loader = match state.games.get_loader(&query.game, &query.loader).await {
-        Some(loader) => loader,
-        None => {
-            return ApiResponse::<Vec<u8>>::error_response(format!(
-                "Loader '{}' not found for game '{}'",
-                query.loader, query.game
-            ))
+        Ok(loader) => loader,
+        Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
+    };
+
+    let range = match query
+        .range
+        .as_deref()
+        .map(VersionRange::from_str)
+        .transpose()
+    {
+        Ok(range) => range,
+        Err(e) => {
+            return ApiResponse::<Vec<u8>>::error_response(WarehouseError::BadRequest(format!(
+                "Invalid version range '{}': {}",
+                query.range.as_deref().unwrap_or_default(),
+                e
+            )))
         }
     };
 
-    let version = Version::new_standard(query.version.clone(), "release".to_string());
+    let version = match state
+        .games
+        .resolve_version(
+            loader.as_ref(),
+            &VersionSelector::from(query.version.as_str()),
+            range.as_ref(),
+        )
+        .await
+    {
+        Ok(Some(version)) => version,
+        Ok(None) => {
+            return ApiResponse::<Vec<u8>>::error_response(WarehouseError::VersionNotFound(
+                query.version.clone(),
+            ))
+        }
+        Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
+    };
+    let options = match BuildOptions::new(query.installer.clone()) {
+        Ok(options) => options,
+        Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
+    };
 
-    let builds = match loader.fetch_builds(&version).await {
+    let builds = match loader.fetch_builds_with(&version, &options).await {
         Ok(builds) => builds,
-        Err(e) => {
-            return ApiResponse::<Vec<u8>>::error_response(format!("Failed to fetch builds: {}", e))
+        Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
+    };
+
+    let selector = query.build_id.as_deref().map(BuildSelector::from);
+    let build = match state.games.resolve_build(builds, selector.as_ref()) {
+        Some(build) => build,
+        None => {
+            return ApiResponse::<Vec<u8>>::error_response(WarehouseError::BuildNotFound(
+                match &query.build_id {
+                    Some(build_id) => format!("Build '{}' not found", build_id),
+                    None => "No builds available for this version".to_string(),
+                },
+            ))
         }
     };
 
-    let build = if let Some(build_id) = &query.build_id {
-        match builds.into_iter().find(|b| b.id() == build_id) {
-            Some(b) => b,
-            None => {
-                return ApiResponse::<Vec<u8>>::error_response(format!(
-                    "Build '{}' not found",
-                    build_id
-                ))
-            }
+    if query.mode.unwrap_or(state.download_mode) == DownloadMode::Redirect {
+        return redirect_upstream(&build);
+    }
+
+    let content_disposition = ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(build.filename())],
+    };
+
+    // HEAD never fetches a build from upstream; an uncached build is described by what
+    // the loader published about it.
+    let download = if req.method() == Method::HEAD {
+        match state
+            .games
+            .cached_download(&query.game, loader.name(), &build)
+            .await
+        {
+            Ok(Some(download)) => download,
+            Ok(None) => return describe_uncached(&req, &build, content_disposition),
+            Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
         }
     } else {
-        match builds.into_iter().next() {
-            Some(b) => b,
-            None => {
-                return ApiResponse::<Vec<u8>>::error_response(
-                    "No builds available for this version".to_string(),
-                )
+        match state
+            .games
+            .download_build(&query.game, loader.name(), &build)
+            .await
+        {
+            Ok(download) => download,
+            Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
+        }
+    };
+
+    match download {
+        Download::Cached(cached) => {
+            // Entries adopted from storage may not have a digest of their own yet.
+            let sha256 = cached
+                .checksums
+                .sha256
+                .as_deref()
+                .or(build.checksums().sha256.as_deref());
+            let validators = Validators::new(sha256, Some(cached.stored));
+            let outcome = conditional::evaluate(&req, &validators, Some(cached.size));
+
+            let range = match outcome {
+                Outcome::NotModified => {
+                    return artifact_response(StatusCode::NOT_MODIFIED, &validators).finish()
+                }
+                Outcome::Unsatisfiable => {
+                    return artifact_response(StatusCode::RANGE_NOT_SATISFIABLE, &validators)
+                        .insert_header(ContentRange(ContentRangeSpec::Bytes {
+                            range: None,
+                            instance_length: Some(cached.size),
+                        }))
+                        .finish()
+                }
+                Outcome::Full => None,
+                Outcome::Partial(range) => Some(range),
+            };
+
+            let body: ByteStream = if req.method() == Method::HEAD {
+                Box::pin(stream::empty())
+            } else {
+                match state.games.cache.open(&cached.key, range.clone()).await {
+                    Ok(body) => body,
+                    Err(e) => return ApiResponse::<Vec<u8>>::error_response(e),
+                }
+            };
+
+            let mut response = match &range {
+                Some(range) => {
+                    let mut response = artifact_response(StatusCode::PARTIAL_CONTENT, &validators);
+                    response.insert_header(ContentRange(ContentRangeSpec::Bytes {
+                        range: Some((range.start, range.end - 1)),
+                        instance_length: Some(cached.size),
+                    }));
+                    response
+                }
+                None => artifact_response(StatusCode::OK, &validators),
+            };
+            response
+                .content_type(JAVA_ARCHIVE)
+                .insert_header(content_disposition)
+                .insert_header((header::ACCEPT_RANGES, "bytes"))
+                .no_chunking(range.map_or(cached.size, |range| range.end - range.start))
+                .streaming(count_served(body, "cache"))
+        }
+        // Ranges cannot be served before the build is in the cache, so the full body is
+        // sent; a client resuming later is served from the cache.
+        Download::Stream { body, size } => {
+            let validators = Validators::new(build.checksums().sha256.as_deref(), None);
+            if conditional::evaluate(&req, &validators, None) == Outcome::NotModified {
+                return artifact_response(StatusCode::NOT_MODIFIED, &validators).finish();
+            }
+
+            let mut response = artifact_response(StatusCode::OK, &validators);
+            response
+                .content_type(JAVA_ARCHIVE)
+                .insert_header(content_disposition);
+            if let Some(size) = size {
+                response.no_chunking(size);
             }
+            response.streaming(count_served(body, "upstream"))
         }
+        Download::Redirect(url) => HttpResponse::TemporaryRedirect()
+            .insert_header((header::LOCATION, url))
+            .finish(),
+    }
+}
+
+/// Answers `HEAD` for a build that is not cached, from its published size and checksums.
+fn describe_uncached(
+    req: &HttpRequest,
+    build: &Build,
+    content_disposition: ContentDisposition,
+) -> HttpResponse {
+    let validators = Validators::new(build.checksums().sha256.as_deref(), None);
+    if conditional::evaluate(req, &validators, None) == Outcome::NotModified {
+        return artifact_response(StatusCode::NOT_MODIFIED, &validators).finish();
+    }
+
+    let mut response = artifact_response(StatusCode::OK, &validators);
+    response
+        .content_type(JAVA_ARCHIVE)
+        .insert_header(content_disposition);
+    // Without a published size the length is left unknown rather than reported as zero.
+    if let Some(size) = build.size() {
+        response.no_chunking(size);
+    }
+    let body: ByteStream = Box::pin(stream::empty());
+    response.streaming(body)
+}
+
+fn parse_range(range: Option<&str>) -> Result<Option<VersionRange>, WarehouseError> {
+    range
+        .map(|range| {
+            VersionRange::from_str(range).map_err(|e| {
+                WarehouseError::BadRequest(format!("Invalid version range '{}': {}", range, e))
+            })
+        })
+        .transpose()
+}
+
+/// Counts the bytes of `body` as they are sent.
+fn count_served(body: ByteStream, source: &str) -> ByteStream {
+    let served = metrics().bytes_served.with_label_values(&[source]);
+    Box::pin(body.inspect_ok(move |chunk| served.inc_by(chunk.len() as u64)))
+}
+
+/// Redirects to the build's upstream URL, passing on the checksums it was published with
+/// so that clients can still verify what they receive.
+fn redirect_upstream(build: &Build) -> HttpResponse {
+    let Some(url) = build.download_url() else {
+        return ApiResponse::<Vec<u8>>::error_response(WarehouseError::Upstream(format!(
+            "no download URL for build '{}'",
+            build.id()
+        )));
     };
 
-    match state.games.download_build(&query.game, &build).await {
-        Ok(data) => HttpResponse::Ok()
-            .append_header(("Content-Type", "application/java-archive"))
-            .append_header((
-                "Content-Disposition",
-                format!("attachment; filename=\"{}\"", build.filename()),
-            ))
-            .body(data),
-        Err(e) => {
-            ApiResponse::<Vec<u8>>::error_response(format!("Failed to download build: {}", e))
+    let mut response = HttpResponse::TemporaryRedirect();
+    response.insert_header((header::LOCATION, url));
+    let checksums = build.checksums();
+    for (name, digest) in [
+        ("X-Checksum-Sha1", &checksums.sha1),
+        ("X-Checksum-Sha256", &checksums.sha256),
+        ("X-Checksum-Md5", &checksums.md5),
+    ] {
+        if let Some(digest) = digest {
+            response.insert_header((name, digest.as_str()));
         }
     }
+    response.finish()
+}
+
+/// Starts a download response carrying the artifact's validators.
+fn artifact_response(status: StatusCode, validators: &Validators) -> HttpResponseBuilder {
+    let mut response = HttpResponse::build(status);
+    if let Some(etag) = &validators.etag {
+        response.insert_header((header::ETAG, etag.as_str()));
+    }
+    if let Some(last_modified) = validators.last_modified_header() {
+        response.insert_header(LastModified(last_modified));
+    }
+    response
 }
 
 
@@ -186,8 +501,7 @@ async fn download_version(
     path = "/api/v1/games",  
     tag = "warehouse",
     responses(
-        (status = 200, description = "List of games", body = Vec<GameInfo>),
-        (status = 400, description = "Error response", body = ErrorResponse)
+        (status = 200, description = "List of games", body = Vec<GameInfo>)
     )
 )]
 #[get("/games")]
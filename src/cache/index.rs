@@ -0,0 +1,203 @@
+use super::CacheEntry;
+use chrono::{DateTime, Utc};
+use redb::{Database, ReadableTable, TableDefinition};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Cache entries keyed by `{game}/{filename}`, with bincode encoded [`CacheEntry`] values.
+const ENTRIES: TableDefinition<&str, &[u8]> = TableDefinition::new("entries");
+
+/// Accesses recorded since the last flush.
+#[derive(Debug, Clone, Copy)]
+struct PendingAccess {
+    accessed: DateTime<Utc>,
+    hits: u64,
+}
+
+/// Metadata of every cached file, kept in a single embedded database.
+///
+/// Cache hits only touch memory; their access times and hit counts are written in one
+/// transaction by [`CacheIndex::flush`]. Reads see pending accesses as if they had been
+/// flushed. All methods block and should be called from a blocking context.
+#[derive(Debug)]
+pub struct CacheIndex {
+    db: Database,
+    pending: Mutex<HashMap<String, PendingAccess>>,
+}
+
+impl CacheIndex {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let db = Database::create(path)?;
+        let tx = db.begin_write()?;
+        tx.open_table(ENTRIES)?;
+        tx.commit()?;
+
+        Ok(Self {
+            db,
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<CacheEntry>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(ENTRIES)?;
+        let Some(value) = table.get(key)? else {
+            return Ok(None);
+        };
+
+        let mut entry = bincode::deserialize::<CacheEntry>(value.value())?;
+        self.apply_pending(key, &mut entry);
+        Ok(Some(entry))
+    }
+
+    /// Lists every entry. Values that cannot be decoded are skipped.
+    pub fn list(&self) -> anyhow::Result<Vec<(String, CacheEntry)>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(ENTRIES)?;
+        let mut result = Vec::new();
+
+        for row in table.iter()? {
+            let (key, value) = row?;
+            let Ok(mut entry) = bincode::deserialize::<CacheEntry>(value.value()) else {
+                continue;
+            };
+            self.apply_pending(key.value(), &mut entry);
+            result.push((key.value().to_string(), entry));
+        }
+
+        Ok(result)
+    }
+
+    /// Stores `entry`, replacing any previous entry and its pending accesses.
+    pub fn insert(&self, key: &str, entry: &CacheEntry) -> anyhow::Result<()> {
+        let value = bincode::serialize(entry)?;
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(ENTRIES)?;
+            table.insert(key, value.as_slice())?;
+        }
+        tx.commit()?;
+        self.pending.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) -> anyhow::Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(ENTRIES)?;
+            table.remove(key)?;
+        }
+        tx.commit()?;
+        self.pending.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    /// Records a cache hit. It is only persisted by the next [`CacheIndex::flush`].
+    pub fn touch(&self, key: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        let access = pending.entry(key.to_string()).or_insert(PendingAccess {
+            accessed: Utc::now(),
+            hits: 0,
+        });
+        access.accessed = Utc::now();
+        access.hits += 1;
+    }
+
+    /// Writes all pending accesses in a single transaction. Accesses to entries that have
+    /// been removed in the meantime are dropped.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(ENTRIES)?;
+            for (key, access) in &pending {
+                let Some(value) = table.get(key.as_str())?.map(|v| v.value().to_vec()) else {
+                    continue;
+                };
+                let Ok(mut entry) = bincode::deserialize::<CacheEntry>(&value) else {
+                    continue;
+                };
+                entry.accessed = entry.accessed.max(access.accessed);
+                entry.hits += access.hits;
+                table.insert(key.as_str(), bincode::serialize(&entry)?.as_slice())?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn apply_pending(&self, key: &str, entry: &mut CacheEntry) {
+        if let Some(access) = self.pending.lock().unwrap().get(key) {
+            entry.accessed = entry.accessed.max(access.accessed);
+            entry.hits += access.hits;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Checksums;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn entry(accessed: DateTime<Utc>) -> CacheEntry {
+        CacheEntry {
+            created: accessed,
+            accessed,
+            checksums: Checksums::default(),
+            size: 10,
+            source_url: None,
+            hits: 0,
+        }
+    }
+
+    #[test]
+    fn buffers_accesses_until_flushed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("index.redb");
+        let stored = Utc::now() - Duration::days(1);
+        {
+            let index = CacheIndex::open(&path).unwrap();
+            index.insert("minecraft/a.jar", &entry(stored)).unwrap();
+            index.touch("minecraft/a.jar");
+            index.touch("minecraft/a.jar");
+
+            // Reads include accesses that are not written yet.
+            let pending = index.get("minecraft/a.jar").unwrap().unwrap();
+            assert_eq!(pending.hits, 2);
+            assert!(pending.accessed > stored);
+        }
+
+        // Accesses that were never flushed are lost with the process.
+        let index = CacheIndex::open(&path).unwrap();
+        let reopened = index.get("minecraft/a.jar").unwrap().unwrap();
+        assert_eq!((reopened.hits, reopened.accessed), (0, stored));
+
+        index.touch("minecraft/a.jar");
+        index.flush().unwrap();
+        drop(index);
+        let index = CacheIndex::open(&path).unwrap();
+        let flushed = index.get("minecraft/a.jar").unwrap().unwrap();
+        assert_eq!(flushed.hits, 1);
+        assert!(flushed.accessed > stored);
+    }
+
+    #[test]
+    fn drops_accesses_to_removed_entries() {
+        let dir = TempDir::new().unwrap();
+        let index = CacheIndex::open(&dir.path().join("index.redb")).unwrap();
+        index.insert("minecraft/a.jar", &entry(Utc::now())).unwrap();
+        index.touch("minecraft/a.jar");
+        index.remove("minecraft/a.jar").unwrap();
+        index.touch("minecraft/b.jar");
+
+        index.flush().unwrap();
+        assert!(index.list().unwrap().is_empty());
+    }
+}
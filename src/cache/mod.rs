@@ -0,0 +1,1102 @@
+mod index;
+
+use crate::checksum::{ChecksumHasher, Checksums};
+use crate::metrics::metrics;
+use crate::storage::{ByteStream, ObjectInfo, StorageBackend};
+use chrono::{DateTime, Duration, Utc};
+use index::CacheIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration as StdDuration;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+const INDEX_FILE: &str = "index.redb";
+const META_SUFFIX: &str = ".meta.bin";
+const TEMP_SUFFIX: &str = ".tmp";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    created: DateTime<Utc>,
+    accessed: DateTime<Utc>,
+    checksums: Checksums,
+    size: u64,
+    source_url: Option<String>,
+    hits: u64,
+}
+
+/// Per-file metadata as stored before the index existed, read once to migrate old caches.
+#[derive(Deserialize)]
+struct SidecarEntry {
+    accessed: DateTime<Utc>,
+    checksums: Checksums,
+    size: u64,
+}
+
+/// The original sidecar layout, which only tracked access time.
+#[derive(Deserialize)]
+struct LegacySidecarEntry {
+    accessed: DateTime<Utc>,
+}
+
+/// A verified cache entry.
+#[derive(Debug, Clone)]
+pub struct CachedFile {
+    pub key: String,
+    pub size: u64,
+    pub checksums: Checksums,
+    pub stored: DateTime<Utc>,
+}
+
+impl CachedFile {
+    fn new(key: String, entry: &CacheEntry) -> Self {
+        Self {
+            key,
+            size: entry.size,
+            checksums: entry.checksums.clone(),
+            stored: entry.created,
+        }
+    }
+}
+
+/// Upper bounds on what the cache, or the share of one loader, may hold. `None` means
+/// unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheQuota {
+    pub max_bytes: Option<u64>,
+    pub max_entries: Option<u64>,
+}
+
+impl CacheQuota {
+    fn is_bounded(&self) -> bool {
+        self.max_bytes.is_some() || self.max_entries.is_some()
+    }
+}
+
+/// Tracks cached artifacts in an index kept under `cache_dir` and stores their data in a
+/// [`StorageBackend`]. Downloads are staged in `cache_dir` before being handed to it.
+#[derive(Debug, Clone)]
+pub struct CacheManager {
+    cache_dir: PathBuf,
+    backend: Arc<dyn StorageBackend>,
+    ttl: Arc<RwLock<Duration>>,
+    quota: Arc<RwLock<CacheQuota>>,
+    /// Quotas of individual loaders, by the `{game}/{loader}/` prefix of their keys.
+    loader_quotas: Arc<RwLock<HashMap<String, CacheQuota>>>,
+    index: Arc<CacheIndex>,
+    pinned: Arc<RwLock<HashSet<String>>>,
+    commit_lock: Arc<Mutex<()>>,
+}
+
+impl CacheManager {
+    pub fn new(
+        cache_dir: PathBuf,
+        backend: Arc<dyn StorageBackend>,
+        ttl_days: u64,
+        quota: CacheQuota,
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&cache_dir)?;
+        let index = CacheIndex::open(&cache_dir.join(INDEX_FILE))?;
+
+        Ok(Self {
+            cache_dir,
+            backend,
+            ttl: Arc::new(RwLock::new(Duration::days(ttl_days as i64))),
+            quota: Arc::new(RwLock::new(quota)),
+            loader_quotas: Arc::new(RwLock::new(HashMap::new())),
+            index: Arc::new(index),
+            pinned: Arc::new(RwLock::new(HashSet::new())),
+            commit_lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Runs `f` against the index on the blocking thread pool.
+    async fn with_index<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&CacheIndex) -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let index = self.index.clone();
+        tokio::task::spawn_blocking(move || f(&index)).await?
+    }
+
+    /// Changes how long entries live and how much the cache may hold. Entries beyond a
+    /// lowered quota are evicted as new ones are stored.
+    pub fn set_limits(&self, ttl_days: u64, quota: CacheQuota) {
+        *self.ttl.write().unwrap() = Duration::days(ttl_days as i64);
+        *self.quota.write().unwrap() = quota;
+    }
+
+    /// Replaces the quotas of individual loaders, which apply on top of the quota of the
+    /// whole cache.
+    pub fn set_loader_quotas(
+        &self,
+        quotas: impl IntoIterator<Item = ((String, String), CacheQuota)>,
+    ) {
+        let quotas = quotas
+            .into_iter()
+            .map(|((game_name, loader_name), quota)| {
+                (cache_key(&game_name, &loader_name, ""), quota)
+            })
+            .collect();
+        *self.loader_quotas.write().unwrap() = quotas;
+    }
+
+    fn ttl(&self) -> Duration {
+        *self.ttl.read().unwrap()
+    }
+
+    /// Replaces the set of entries that are exempt from expiry and eviction.
+    pub fn set_pinned(&self, entries: impl IntoIterator<Item = (String, String, String)>) {
+        let pinned = entries
+            .into_iter()
+            .map(|(game_name, loader_name, filename)| {
+                cache_key(&game_name, &loader_name, &filename)
+            })
+            .collect();
+        *self.pinned.write().unwrap() = pinned;
+    }
+
+    fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.read().unwrap().contains(key)
+    }
+
+    /// Opens a cache entry returned by [`CacheManager::get`] for reading, optionally only
+    /// the given byte range of it.
+    pub async fn open(&self, key: &str, range: Option<Range<u64>>) -> anyhow::Result<ByteStream> {
+        self.backend
+            .get(key, range)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("cache entry {} disappeared from storage", key))
+    }
+
+    /// Returns a URL clients can download a cache entry from directly, if the storage
+    /// backend supports it.
+    pub async fn presign(&self, key: &str, ttl: StdDuration) -> anyhow::Result<Option<String>> {
+        self.backend.presign(key, ttl).await
+    }
+
+    /// Persists access times and hit counts recorded since the last flush.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        self.with_index(|index| index.flush()).await
+    }
+
+    /// Brings the cache back to a consistent state after an unclean shutdown: removes
+    /// temporary files from interrupted writes and index entries whose data is gone.
+    /// Stored objects the index does not know about are dropped from local storage and
+    /// adopted from remote storage, where they may have outlived the node that wrote them.
+    /// Entries of caches that still use per-file `.meta.bin` sidecars are imported into
+    /// the index and the sidecars deleted.
+    pub async fn recover(&self) -> anyhow::Result<()> {
+        let mut sidecars = Vec::new();
+        for path in files_below(&self.cache_dir).await? {
+            if is_temp(&path) {
+                info!("removing interrupted cache write {}", path.display());
+                fs::remove_file(&path).await.ok();
+            } else if is_meta(&path) {
+                sidecars.push(path);
+            }
+        }
+
+        let objects = self
+            .backend
+            .list()
+            .await?
+            .into_iter()
+            .filter(|object| !is_meta(Path::new(&object.key)) && !is_temp(Path::new(&object.key)))
+            .collect::<Vec<_>>();
+
+        for object in &objects {
+            let lookup = object.key.clone();
+            if self
+                .with_index(move |index| index.get(&lookup))
+                .await?
+                .is_some()
+            {
+                continue;
+            }
+
+            let key = object.key.clone();
+            match self.backend.local_path(&key) {
+                Some(path) => match read_sidecar(&path, object.size).await {
+                    Some(entry) => {
+                        info!("migrating cache entry {} into the index", key);
+                        self.with_index(move |index| index.insert(&key, &entry))
+                            .await?;
+                    }
+                    None => {
+                        warn!("removing cache file without index entry {}", path.display());
+                        self.backend.delete(&key).await.ok();
+                    }
+                },
+                None => {
+                    info!("adopting stored cache entry {}", key);
+                    let entry = adopted_entry(object);
+                    self.with_index(move |index| index.insert(&key, &entry))
+                        .await?;
+                }
+            }
+        }
+
+        for path in sidecars {
+            fs::remove_file(path).await.ok();
+        }
+
+        let stored = objects
+            .into_iter()
+            .map(|object| object.key)
+            .collect::<HashSet<_>>();
+        for (key, _) in self.with_index(|index| index.list()).await? {
+            if !stored.contains(&key) {
+                warn!("removing cache index entry without data {}", key);
+                self.with_index(move |index| index.remove(&key)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_loader_path(&self, game_name: &str, loader_name: &str) -> PathBuf {
+        let path = self.cache_dir.join(game_name).join(loader_name);
+        fs::create_dir_all(&path).await.ok();
+        path
+    }
+
+    /// Returns the cached file if it is fresh and still matches both the checksums
+    /// recorded when it was stored and the `expected` ones. Data is verified when it is
+    /// written, so hits only compare digests and the stored size; see
+    /// [`CacheManager::verify`]. Corrupted entries are dropped. Hits are recorded in memory
+    /// and persisted by [`CacheManager::flush`].
+    pub async fn get(
+        &self,
+        game_name: &str,
+        loader_name: &str,
+        filename: &str,
+        expected: &Checksums,
+    ) -> anyhow::Result<Option<CachedFile>> {
+        let key = cache_key(game_name, loader_name, filename);
+
+        let lookup = key.clone();
+        let Some(entry) = self.with_index(move |index| index.get(&lookup)).await? else {
+            metrics().cache_misses.inc();
+            return Ok(None);
+        };
+
+        if self.is_pinned(&key) || Utc::now() - entry.accessed < self.ttl() {
+            match self.verify(&key, &entry, expected).await {
+                Ok(true) => {
+                    self.index.touch(&key);
+                    metrics().cache_hits.inc();
+                    return Ok(Some(CachedFile::new(key, &entry)));
+                }
+                Ok(false) => {}
+                Err(e) => warn!("dropping corrupted cache entry {}: {}", key, e),
+            }
+        }
+
+        // The index entry goes first so the entry never looks valid while its data is
+        // being removed.
+        let removed = key.clone();
+        self.with_index(move |index| index.remove(&removed)).await?;
+        self.backend.delete(&key).await.ok();
+
+        metrics().cache_misses.inc();
+        Ok(None)
+    }
+
+    /// Checks a stored entry against its recorded and expected checksums and its recorded
+    /// size. Returns `false` if its data is missing.
+    ///
+    /// Committed entries record the digests their data was verified against, so only those
+    /// are compared. A local file is hashed once when the index cannot vouch for it, as for
+    /// entries migrated or adopted without a SHA-256 or when a digest is expected that was
+    /// not recorded, and the result is recorded for later hits. Remote objects are trusted
+    /// to the storage service's own integrity checks.
+    async fn verify(
+        &self,
+        key: &str,
+        entry: &CacheEntry,
+        expected: &Checksums,
+    ) -> anyhow::Result<bool> {
+        match self.backend.stat(key).await? {
+            Some(object) if object.size != entry.size => anyhow::bail!(
+                "size mismatch: expected {}, got {}",
+                entry.size,
+                object.size
+            ),
+            Some(_) => {}
+            None => return Ok(false),
+        }
+        expected.verify(&entry.checksums)?;
+
+        let Some(path) = self.backend.local_path(key) else {
+            return Ok(true);
+        };
+        if entry.checksums.sha256.is_some() && entry.checksums.covers(expected) {
+            return Ok(true);
+        }
+
+        let mut checksums = expected.clone();
+        checksums.merge(&entry.checksums);
+        let mut verified = entry.clone();
+        verified.checksums = hash_file(&path, &checksums).await?;
+        verified.checksums.merge(&entry.checksums);
+
+        let key = key.to_string();
+        self.with_index(move |index| index.insert(&key, &verified))
+            .await?;
+        Ok(true)
+    }
+
+    /// Starts writing a new entry. Data is staged in a temporary file that is only handed
+    /// to the storage backend once [`CacheWriter::commit`] has verified it against
+    /// `expected`.
+    pub async fn writer(
+        &self,
+        game_name: &str,
+        loader_name: &str,
+        filename: &str,
+        expected: &Checksums,
+        source_url: Option<&str>,
+    ) -> anyhow::Result<CacheWriter> {
+        let loader_path = self.get_loader_path(game_name, loader_name).await;
+        let temp_path = temp_path(&loader_path.join(filename));
+        let file = fs::File::create(&temp_path).await?;
+
+        Ok(CacheWriter {
+            manager: self.clone(),
+            key: cache_key(game_name, loader_name, filename),
+            file,
+            temp_path,
+            source_url: source_url.map(str::to_string),
+            expected: expected.clone(),
+            size: 0,
+            committed: false,
+        })
+    }
+
+    /// Whether an entry of `size` bytes for the given loader is small enough to be stored
+    /// within the quotas of the cache and of the loader.
+    pub fn fits(&self, game_name: &str, loader_name: &str, size: u64) -> bool {
+        self.quotas(&cache_key(game_name, loader_name, ""))
+            .iter()
+            .all(|(_, quota)| quota.max_bytes.is_none_or(|max| size <= max))
+    }
+
+    /// The bounded quotas that apply to `key`, each with the key prefix it covers: the
+    /// quota of its loader first, then that of the whole cache.
+    fn quotas(&self, key: &str) -> Vec<(String, CacheQuota)> {
+        let mut quotas = Vec::new();
+        if let Some(prefix) = loader_prefix(key) {
+            if let Some(quota) = self.loader_quotas.read().unwrap().get(prefix) {
+                quotas.push((prefix.to_string(), *quota));
+            }
+        }
+        quotas.push((String::new(), *self.quota.read().unwrap()));
+        quotas.retain(|(_, quota)| quota.is_bounded());
+        quotas
+    }
+
+    /// Evicts least recently used entries until an entry of `incoming` bytes fits within
+    /// the quota of its loader and of the whole cache, and returns whether it does.
+    /// `replacing` is the key the new entry will be stored under; an existing entry there
+    /// is not counted since it is about to be overwritten. Pinned entries are never
+    /// evicted, so nothing is evicted for an entry that would not fit in either quota even
+    /// once every unpinned entry is gone.
+    async fn make_room(&self, replacing: &str, incoming: u64) -> anyhow::Result<bool> {
+        let quotas = self.quotas(replacing);
+        if quotas.is_empty() {
+            return Ok(true);
+        }
+
+        let (pinned, mut entries): (Vec<_>, Vec<_>) = self
+            .with_index(|index| index.list())
+            .await?
+            .into_iter()
+            .filter(|(key, _)| key != replacing)
+            .partition(|(key, _)| self.is_pinned(key));
+
+        for (prefix, quota) in &quotas {
+            let scope = quota_scope(prefix);
+            if let Some(max) = quota.max_bytes.filter(|max| incoming > *max) {
+                warn!(
+                    "not caching {}: its {} bytes exceed the {} of {} bytes",
+                    replacing, incoming, scope, max
+                );
+                return Ok(false);
+            }
+
+            let pinned = pinned
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix.as_str()));
+            let (bytes, count) = totals(pinned, incoming);
+            if quota.max_bytes.is_some_and(|max| bytes > max)
+                || quota.max_entries.is_some_and(|max| count > max)
+            {
+                warn!(
+                    "not caching {}: the pinned latest stable builds leave no room for it \
+                     within the {}",
+                    replacing, scope
+                );
+                return Ok(false);
+            }
+        }
+
+        // Entries evicted for the loader quota also count towards the cache quota, so
+        // that one is only applied to what is left.
+        entries.sort_by_key(|(_, entry)| entry.accessed);
+        for (prefix, quota) in quotas {
+            let in_scope = |key: &String| key.starts_with(prefix.as_str());
+            let (mut total_bytes, mut total_entries) = totals(
+                pinned
+                    .iter()
+                    .chain(&entries)
+                    .filter(|(key, _)| in_scope(key)),
+                incoming,
+            );
+
+            let mut evicted = HashSet::new();
+            for (key, entry) in entries.iter().filter(|(key, _)| in_scope(key)) {
+                let over_bytes = quota.max_bytes.is_some_and(|max| total_bytes > max);
+                let over_entries = quota.max_entries.is_some_and(|max| total_entries > max);
+                if !over_bytes && !over_entries {
+                    break;
+                }
+
+                info!("evicting {} from cache", key);
+                let removed = key.clone();
+                self.with_index(move |index| index.remove(&removed)).await?;
+                self.backend.delete(key).await.ok();
+                metrics()
+                    .cache_evictions
+                    .with_label_values(&["quota"])
+                    .inc();
+                total_bytes -= entry.size;
+                total_entries -= 1;
+                evicted.insert(key.clone());
+            }
+            entries.retain(|(key, _)| !evicted.contains(key));
+        }
+
+        Ok(true)
+    }
+
+    /// Checks that the cache directory accepts writes, which every download needs to stage
+    /// its data.
+    pub async fn check_writable(&self) -> anyhow::Result<()> {
+        let probe = temp_path(&self.cache_dir.join("probe"));
+        fs::write(&probe, b"").await?;
+        fs::remove_file(&probe).await?;
+        Ok(())
+    }
+
+    /// Returns the number of entries and their total size in bytes.
+    pub async fn usage(&self) -> anyhow::Result<(usize, u64)> {
+        let entries = self.with_index(|index| index.list()).await?;
+        let bytes = entries.iter().map(|(_, entry)| entry.size).sum();
+        Ok((entries.len(), bytes))
+    }
+
+    pub async fn cleanup(&self) -> anyhow::Result<()> {
+        self.flush().await?;
+
+        let now = Utc::now();
+        let ttl = self.ttl();
+        for (key, entry) in self.with_index(|index| index.list()).await? {
+            if !self.is_pinned(&key) && now - entry.accessed > ttl {
+                let removed = key.clone();
+                self.with_index(move |index| index.remove(&removed)).await?;
+                self.backend.delete(&key).await.ok();
+                metrics()
+                    .cache_evictions
+                    .with_label_values(&["expired"])
+                    .inc();
+            }
+        }
+
+        let mut entries = fs::read_dir(&self.cache_dir).await?;
+        while let Some(game_dir) = entries.next_entry().await? {
+            if game_dir.file_type().await?.is_dir() {
+                remove_empty_dirs(&game_dir.path()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An in-progress cache entry. Dropping it without committing discards the partial file.
+pub struct CacheWriter {
+    manager: CacheManager,
+    key: String,
+    file: fs::File,
+    temp_path: PathBuf,
+    source_url: Option<String>,
+    expected: Checksums,
+    size: u64,
+    committed: bool,
+}
+
+impl CacheWriter {
+    pub async fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.file.write_all(data).await?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Stores the written data, returning the new cache entry. `checksums` are the digests
+    /// of that data, which the caller has already verified with a [`ChecksumHasher`] as it
+    /// passed through, so that a failed cache write does not stop verification.
+    ///
+    /// The previous index entry is removed before the data replaces the old object and the
+    /// new entry is only inserted afterwards, so the index never vouches for data it has
+    /// not verified. A crash in between leaves an object without an index entry, which
+    /// [`CacheManager::recover`] removes or adopts.
+    pub async fn commit(mut self, mut checksums: Checksums) -> anyhow::Result<CachedFile> {
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        checksums.merge(&self.expected);
+
+        let now = Utc::now();
+        let entry = CacheEntry {
+            created: now,
+            accessed: now,
+            checksums,
+            size: self.size,
+            source_url: self.source_url.take(),
+            hits: 0,
+        };
+
+        // Serialised so that concurrent commits do not both count the same free space.
+        let manager = self.manager.clone();
+        let _guard = manager.commit_lock.lock().await;
+        if !manager.make_room(&self.key, self.size).await? {
+            anyhow::bail!("{} does not fit within the cache quota", self.key);
+        }
+
+        let key = self.key.clone();
+        manager.with_index(move |index| index.remove(&key)).await?;
+        manager.backend.put(&self.key, &self.temp_path).await?;
+        self.committed = true;
+
+        let cached = CachedFile::new(self.key.clone(), &entry);
+        let key = self.key.clone();
+        manager
+            .with_index(move |index| index.insert(&key, &entry))
+            .await?;
+
+        Ok(cached)
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        if !self.committed {
+            std::fs::remove_file(&self.temp_path).ok();
+        }
+    }
+}
+
+fn cache_key(game_name: &str, loader_name: &str, filename: &str) -> String {
+    format!("{}/{}/{}", game_name, loader_name, filename)
+}
+
+/// The `{game}/{loader}/` prefix of a key. Entries cached before keys included the loader
+/// have none.
+fn loader_prefix(key: &str) -> Option<&str> {
+    key.match_indices('/').nth(1).map(|(i, _)| &key[..=i])
+}
+
+/// Names the quota covering `prefix` in log messages.
+fn quota_scope(prefix: &str) -> String {
+    match prefix.strip_suffix('/') {
+        Some(loader) => format!("quota of {}", loader),
+        None => "cache quota".to_string(),
+    }
+}
+
+/// The total size and number of `entries` together with an incoming entry of `incoming`
+/// bytes.
+fn totals<'a>(
+    entries: impl Iterator<Item = &'a (String, CacheEntry)>,
+    incoming: u64,
+) -> (u64, u64) {
+    entries.fold((incoming, 1), |(bytes, count), (_, entry)| {
+        (bytes + entry.size, count + 1)
+    })
+}
+
+/// Lists the files in the subdirectories of `root`, at any depth.
+async fn files_below(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() && dir != root {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Removes `dir` and the directories below it that hold no files. Returns whether `dir`
+/// was removed.
+async fn remove_empty_dirs(dir: &Path) -> anyhow::Result<bool> {
+    let mut empty = true;
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let removed =
+            entry.file_type().await?.is_dir() && Box::pin(remove_empty_dirs(&entry.path())).await?;
+        empty &= removed;
+    }
+
+    Ok(empty && fs::remove_dir(dir).await.is_ok())
+}
+
+/// An index entry for an object found in storage without one. Its checksums are unknown,
+/// and it is treated as freshly accessed so that it is not expired right away.
+fn adopted_entry(object: &ObjectInfo) -> CacheEntry {
+    CacheEntry {
+        created: object.last_modified,
+        accessed: Utc::now(),
+        checksums: Checksums::default(),
+        size: object.size,
+        source_url: None,
+        hits: 0,
+    }
+}
+
+/// Reads the sidecar of a data file cached before the index existed, in either the
+/// `{filename}.meta.bin` or the older `{stem}.meta.bin` layout.
+async fn read_sidecar(path: &Path, size: u64) -> Option<CacheEntry> {
+    for sidecar in [meta_path(path), path.with_extension("meta.bin")] {
+        let Ok(content) = fs::read(&sidecar).await else {
+            continue;
+        };
+
+        let (accessed, checksums, recorded_size) =
+            match bincode::deserialize::<SidecarEntry>(&content) {
+                Ok(entry) => (entry.accessed, entry.checksums, entry.size),
+                Err(_) => match bincode::deserialize::<LegacySidecarEntry>(&content) {
+                    Ok(entry) => (entry.accessed, Checksums::default(), 0),
+                    Err(_) => continue,
+                },
+            };
+
+        return Some(CacheEntry {
+            created: accessed,
+            accessed,
+            checksums,
+            size: if recorded_size == 0 {
+                size
+            } else {
+                recorded_size
+            },
+            source_url: None,
+            hits: 0,
+        });
+    }
+
+    None
+}
+
+/// Hashes a file in chunks so that large artifacts are never held in memory at once.
+async fn hash_file(path: &Path, expected: &Checksums) -> anyhow::Result<Checksums> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = ChecksumHasher::new(expected);
+    let mut buf = vec![0u8; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    hasher.finish()
+}
+
+fn meta_path(file_path: &Path) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(META_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// A unique hidden file next to `file_path`, so that renaming it over `file_path` is atomic.
+fn temp_path(file_path: &Path) -> PathBuf {
+    let filename = file_path.file_name().unwrap_or_default().to_string_lossy();
+    file_path.with_file_name(format!(
+        ".{}.{}.{}{}",
+        filename,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed),
+        TEMP_SUFFIX
+    ))
+}
+
+fn is_meta(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(META_SUFFIX)
+}
+
+fn is_temp(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(TEMP_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+    use sha1::Sha1;
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+
+    const GAME: &str = "minecraft";
+    const LOADER: &str = "paper";
+    const FILE: &str = "server.jar";
+    const DATA: &[u8] = b"server jar";
+
+    fn manager(dir: &TempDir) -> CacheManager {
+        let root = dir.path().to_path_buf();
+        let backend = Arc::new(LocalStorage::new(root.clone()));
+        CacheManager::new(root, backend, 7, CacheQuota::default()).unwrap()
+    }
+
+    fn sha256(data: &[u8]) -> Checksums {
+        Checksums {
+            sha256: Some(hex::encode(Sha256::digest(data))),
+            ..Checksums::default()
+        }
+    }
+
+    fn sha1(data: &[u8]) -> Checksums {
+        Checksums {
+            sha1: Some(hex::encode(Sha1::digest(data))),
+            ..Checksums::default()
+        }
+    }
+
+    async fn store(cache: &CacheManager, data: &[u8]) -> CachedFile {
+        store_as(cache, FILE, data).await.unwrap()
+    }
+
+    async fn store_as(
+        cache: &CacheManager,
+        filename: &str,
+        data: &[u8],
+    ) -> anyhow::Result<CachedFile> {
+        store_for(cache, LOADER, filename, data).await
+    }
+
+    async fn store_for(
+        cache: &CacheManager,
+        loader_name: &str,
+        filename: &str,
+        data: &[u8],
+    ) -> anyhow::Result<CachedFile> {
+        let mut writer = cache
+            .writer(GAME, loader_name, filename, &Checksums::default(), None)
+            .await?;
+        writer.write(data).await?;
+        writer.commit(sha256(data)).await
+    }
+
+    async fn keys(cache: &CacheManager) -> Vec<String> {
+        let mut keys = cache
+            .with_index(|index| index.list())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    fn limit_bytes(cache: &CacheManager, max_bytes: u64) {
+        let quota = CacheQuota {
+            max_bytes: Some(max_bytes),
+            max_entries: None,
+        };
+        cache.set_limits(7, quota);
+    }
+
+    /// Stores `data` the way caches from before the index did, without known digests.
+    async fn store_unverified(cache: &CacheManager, data: &[u8]) {
+        let key = cache_key(GAME, LOADER, FILE);
+        std::fs::create_dir_all(cache.cache_dir.join(GAME).join(LOADER)).unwrap();
+        std::fs::write(cache.cache_dir.join(&key), data).unwrap();
+        let entry = adopted_entry(&cache.backend.stat(&key).await.unwrap().unwrap());
+        cache.index.insert(&key, &entry).unwrap();
+    }
+
+    async fn hit(cache: &CacheManager, expected: &Checksums) -> bool {
+        cache
+            .get(GAME, LOADER, FILE, expected)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    fn assert_removed(cache: &CacheManager) {
+        let key = cache_key(GAME, LOADER, FILE);
+        assert!(cache.index.get(&key).unwrap().is_none());
+        assert!(!cache.cache_dir.join(key).exists());
+    }
+
+    #[tokio::test]
+    async fn serves_entries_matching_the_expected_checksums() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store(&cache, DATA).await;
+
+        let cached = cache
+            .get(GAME, LOADER, FILE, &sha256(DATA))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.size, DATA.len() as u64);
+        assert_eq!(cached.checksums, sha256(DATA));
+        assert!(hit(&cache, &Checksums::default()).await);
+    }
+
+    #[tokio::test]
+    async fn drops_entries_with_mismatching_checksums() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store(&cache, DATA).await;
+
+        assert!(!hit(&cache, &sha256(b"other jar")).await);
+        assert_removed(&cache);
+    }
+
+    #[tokio::test]
+    async fn drops_entries_whose_size_changed() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store(&cache, DATA).await;
+        std::fs::write(
+            cache.cache_dir.join(cache_key(GAME, LOADER, FILE)),
+            b"truncated",
+        )
+        .unwrap();
+
+        assert!(!hit(&cache, &sha256(DATA)).await);
+        assert_removed(&cache);
+    }
+
+    #[tokio::test]
+    async fn hashes_unverified_entries_once() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store_unverified(&cache, DATA).await;
+
+        assert!(hit(&cache, &sha1(DATA)).await);
+        let entry = cache
+            .index
+            .get(&cache_key(GAME, LOADER, FILE))
+            .unwrap()
+            .unwrap();
+        let mut recorded = sha1(DATA);
+        recorded.merge(&sha256(DATA));
+        assert_eq!(entry.checksums, recorded);
+
+        // Later hits trust the recorded digests instead of reading the file again.
+        std::fs::write(
+            cache.cache_dir.join(cache_key(GAME, LOADER, FILE)),
+            b"server jaR",
+        )
+        .unwrap();
+        assert!(hit(&cache, &sha1(DATA)).await);
+        assert!(!hit(&cache, &sha256(b"x")).await);
+    }
+
+    #[tokio::test]
+    async fn drops_unverified_entries_that_fail_hashing() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store_unverified(&cache, DATA).await;
+
+        assert!(!hit(&cache, &sha1(b"other")).await);
+        assert_removed(&cache);
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used_entries() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        limit_bytes(&cache, 12);
+        store_as(&cache, "a.jar", b"aaaaa").await.unwrap();
+        store_as(&cache, "b.jar", b"bbbbb").await.unwrap();
+        assert!(cache
+            .get(GAME, LOADER, "a.jar", &Checksums::default())
+            .await
+            .unwrap()
+            .is_some());
+
+        store_as(&cache, "c.jar", b"ccccc").await.unwrap();
+        assert_eq!(
+            keys(&cache).await,
+            ["minecraft/paper/a.jar", "minecraft/paper/c.jar"]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_entries_larger_than_the_quota() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        limit_bytes(&cache, 12);
+        store_as(&cache, "a.jar", b"aaaaa").await.unwrap();
+        store_as(&cache, "b.jar", b"bbbbb").await.unwrap();
+
+        assert!(!cache.fits(GAME, LOADER, 13));
+        assert!(store_as(&cache, "c.jar", &[0; 13]).await.is_err());
+        assert_eq!(
+            keys(&cache).await,
+            ["minecraft/paper/a.jar", "minecraft/paper/b.jar"]
+        );
+        assert!(!cache
+            .cache_dir
+            .join(cache_key(GAME, LOADER, "c.jar"))
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn skips_entries_that_pinned_entries_leave_no_room_for() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store_as(&cache, "a.jar", b"aaaaa").await.unwrap();
+        store_as(&cache, "pinned.jar", b"pinned jar").await.unwrap();
+        cache.set_pinned([(
+            GAME.to_string(),
+            LOADER.to_string(),
+            "pinned.jar".to_string(),
+        )]);
+        limit_bytes(&cache, 12);
+
+        assert!(cache.fits(GAME, LOADER, 5));
+        assert!(store_as(&cache, "b.jar", b"bbbbb").await.is_err());
+        assert_eq!(
+            keys(&cache).await,
+            ["minecraft/paper/a.jar", "minecraft/paper/pinned.jar"]
+        );
+    }
+
+    #[tokio::test]
+    async fn evicts_within_the_loader_quota() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        let quota = CacheQuota {
+            max_bytes: None,
+            max_entries: Some(2),
+        };
+        cache.set_loader_quotas([((GAME.to_string(), LOADER.to_string()), quota)]);
+        store_as(&cache, "a.jar", b"aaaaa").await.unwrap();
+        store_for(&cache, "purpur", "x.jar", b"xxxxx")
+            .await
+            .unwrap();
+        store_for(&cache, "purpur", "y.jar", b"yyyyy")
+            .await
+            .unwrap();
+        store_as(&cache, "b.jar", b"bbbbb").await.unwrap();
+
+        store_as(&cache, "c.jar", b"ccccc").await.unwrap();
+        assert_eq!(
+            keys(&cache).await,
+            [
+                "minecraft/paper/b.jar",
+                "minecraft/paper/c.jar",
+                "minecraft/purpur/x.jar",
+                "minecraft/purpur/y.jar"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_entries_larger_than_the_loader_quota() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        let quota = CacheQuota {
+            max_bytes: Some(5),
+            max_entries: None,
+        };
+        cache.set_loader_quotas([((GAME.to_string(), LOADER.to_string()), quota)]);
+
+        assert!(!cache.fits(GAME, LOADER, 6));
+        assert!(cache.fits(GAME, "purpur", 6));
+        assert!(store_as(&cache, "a.jar", b"aaaaaa").await.is_err());
+        store_for(&cache, "purpur", "a.jar", b"aaaaaa")
+            .await
+            .unwrap();
+        assert_eq!(keys(&cache).await, ["minecraft/purpur/a.jar"]);
+    }
+
+    #[tokio::test]
+    async fn removes_empty_loader_directories() {
+        let dir = TempDir::new().unwrap();
+        let cache = manager(&dir);
+        store_as(&cache, "a.jar", b"aaaaa").await.unwrap();
+        std::fs::create_dir_all(dir.path().join(GAME).join("purpur")).unwrap();
+
+        cache.cleanup().await.unwrap();
+        assert!(dir.path().join(cache_key(GAME, LOADER, "a.jar")).exists());
+        assert!(!dir.path().join(GAME).join("purpur").exists());
+    }
+
+    #[tokio::test]
+    async fn migrates_sidecars_into_the_index() {
+        let dir = TempDir::new().unwrap();
+        let game_dir = dir.path().join(GAME);
+        std::fs::create_dir_all(&game_dir).unwrap();
+        let accessed = Utc::now() - Duration::days(2);
+
+        // `{filename}.meta.bin` with checksums, and the older `{stem}.meta.bin`.
+        std::fs::write(game_dir.join("a.jar"), b"aaaaa").unwrap();
+        let sidecar = bincode::serialize(&(accessed, sha256(b"aaaaa"), 5u64)).unwrap();
+        std::fs::write(game_dir.join("a.jar.meta.bin"), sidecar).unwrap();
+        std::fs::write(game_dir.join("b.jar"), b"bbb").unwrap();
+        let legacy = bincode::serialize(&accessed).unwrap();
+        std::fs::write(game_dir.join("b.meta.bin"), legacy).unwrap();
+        std::fs::write(game_dir.join("orphan.jar"), b"orphan").unwrap();
+        std::fs::write(temp_path(&game_dir.join("c.jar")), b"partial").unwrap();
+
+        let cache = manager(&dir);
+        let gone = adopted_entry(&ObjectInfo {
+            key: "minecraft/gone.jar".to_string(),
+            size: 1,
+            last_modified: accessed,
+        });
+        cache.index.insert("minecraft/gone.jar", &gone).unwrap();
+        cache.recover().await.unwrap();
+
+        assert_eq!(keys(&cache).await, ["minecraft/a.jar", "minecraft/b.jar"]);
+        let a = cache.index.get("minecraft/a.jar").unwrap().unwrap();
+        assert_eq!((a.accessed, a.size), (accessed, 5));
+        assert_eq!(a.checksums, sha256(b"aaaaa"));
+        let b = cache.index.get("minecraft/b.jar").unwrap().unwrap();
+        assert_eq!((b.accessed, b.size), (accessed, 3));
+        assert_eq!(b.checksums, Checksums::default());
+
+        let mut files = std::fs::read_dir(&game_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["a.jar", "b.jar"]);
+    }
+}
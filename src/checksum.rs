@@ -0,0 +1,155 @@
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Hex-encoded digests an upstream publishes for an artifact. Any of them may be missing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksums {
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+    pub md5: Option<String>,
+}
+
+impl Checksums {
+    /// Fills in any digest missing from `self` with the one from `other`.
+    pub fn merge(&mut self, other: &Checksums) {
+        if self.sha1.is_none() {
+            self.sha1 = other.sha1.clone();
+        }
+        if self.sha256.is_none() {
+            self.sha256 = other.sha256.clone();
+        }
+        if self.md5.is_none() {
+            self.md5 = other.md5.clone();
+        }
+    }
+
+    /// Whether every digest in `other` is also known to `self`.
+    pub fn covers(&self, other: &Checksums) -> bool {
+        (other.sha1.is_none() || self.sha1.is_some())
+            && (other.sha256.is_none() || self.sha256.is_some())
+            && (other.md5.is_none() || self.md5.is_some())
+    }
+
+    /// Returns an error if any digest known to both `self` and `actual` differs.
+    pub fn verify(&self, actual: &Checksums) -> anyhow::Result<()> {
+        check("sha1", &self.sha1, &actual.sha1)?;
+        check("sha256", &self.sha256, &actual.sha256)?;
+        check("md5", &self.md5, &actual.md5)
+    }
+}
+
+/// Incrementally hashes an artifact and checks it against the expected [`Checksums`].
+///
+/// SHA-256 is always computed so that artifacts without a published digest can still
+/// be checked for corruption once they are in the cache.
+pub struct ChecksumHasher {
+    expected: Checksums,
+    sha1: Option<Sha1>,
+    sha256: Sha256,
+    md5: Option<Md5>,
+}
+
+impl ChecksumHasher {
+    pub fn new(expected: &Checksums) -> Self {
+        Self {
+            expected: expected.clone(),
+            sha1: expected.sha1.as_ref().map(|_| Sha1::new()),
+            sha256: Sha256::new(),
+            md5: expected.md5.as_ref().map(|_| Md5::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        if let Some(sha1) = &mut self.sha1 {
+            sha1.update(data);
+        }
+        self.sha256.update(data);
+        if let Some(md5) = &mut self.md5 {
+            md5.update(data);
+        }
+    }
+
+    /// Returns the computed digests, or an error if any of them differs from the expected one.
+    pub fn finish(self) -> anyhow::Result<Checksums> {
+        let actual = Checksums {
+            sha1: self.sha1.map(|h| hex::encode(h.finalize())),
+            sha256: Some(hex::encode(self.sha256.finalize())),
+            md5: self.md5.map(|h| hex::encode(h.finalize())),
+        };
+
+        self.expected.verify(&actual)?;
+        Ok(actual)
+    }
+}
+
+fn check(
+    algorithm: &str,
+    expected: &Option<String>,
+    actual: &Option<String>,
+) -> anyhow::Result<()> {
+    if let (Some(expected), Some(actual)) = (expected, actual) {
+        if !expected.eq_ignore_ascii_case(actual) {
+            anyhow::bail!(
+                "{} mismatch: expected {}, got {}",
+                algorithm,
+                expected,
+                actual
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA_SHA256: &str = "5c8b2b3aa2c96a5d8c2a0d9bfa4e3e0e4bb13e84d0e0cd3cf1ea5ac1a5ee1e1a";
+
+    fn hash(expected: &Checksums, data: &[u8]) -> anyhow::Result<Checksums> {
+        let mut hasher = ChecksumHasher::new(expected);
+        for chunk in data.chunks(3) {
+            hasher.update(chunk);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn rejects_data_that_does_not_match_a_published_digest() {
+        let actual = hash(&Checksums::default(), b"server jar").unwrap();
+        assert!(actual.sha1.is_none() && actual.md5.is_none());
+
+        let upper = Checksums {
+            sha256: actual.sha256.as_ref().map(|d| d.to_uppercase()),
+            ..Checksums::default()
+        };
+        assert!(hash(&upper, b"server jar").is_ok());
+
+        let wrong = Checksums {
+            sha256: Some(DATA_SHA256.to_string()),
+            ..Checksums::default()
+        };
+        let err = hash(&wrong, b"server jar").unwrap_err();
+        assert!(err.to_string().starts_with("sha256 mismatch"));
+    }
+
+    #[test]
+    fn covers_only_digests_it_knows() {
+        let sha256 = Checksums {
+            sha256: Some("a".to_string()),
+            ..Checksums::default()
+        };
+        let sha1 = Checksums {
+            sha1: Some("b".to_string()),
+            ..Checksums::default()
+        };
+        assert!(sha256.covers(&Checksums::default()));
+        assert!(!sha256.covers(&sha1));
+
+        let mut both = sha256.clone();
+        both.merge(&sha1);
+        assert!(both.covers(&sha1) && both.covers(&sha256));
+    }
+}
@@ -1,7 +1,21 @@
-use config::{Config, Environment};
-use serde::Deserialize;
+use crate::api::v1::models::DownloadMode;
+use crate::cache::CacheQuota;
+use crate::game::Game;
+use crate::games::common::Upstream;
+use anyhow::{bail, Context};
+use config::{Config, Environment, File};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Debug, Deserialize)]
+/// Settings are read from an optional config file, in any format the `config` crate
+/// recognizes by extension (TOML and YAML among them), overridden by `WAREHOUSE_*`
+/// environment variables. Unknown keys are rejected so that typos do not go unnoticed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Settings {
     #[serde(default = "default_bind_address")]
     pub bind_address: String,
@@ -11,8 +25,120 @@ pub struct Settings {
     pub log_level: String,
     #[serde(default = "default_cache_ttl")]
     pub cache_ttl: u64,
+    #[serde(default)]
+    pub cache_max_bytes: Option<u64>,
+    #[serde(default)]
+    pub cache_max_entries: Option<u64>,
+    #[serde(default = "default_metadata_ttl")]
+    pub metadata_ttl: u64,
+    #[serde(default)]
+    pub loader_metadata_ttl: HashMap<String, u64>,
+    #[serde(default = "default_storage_backend")]
+    pub storage_backend: String,
+    #[serde(default)]
+    pub s3_bucket: Option<String>,
+    #[serde(default)]
+    pub s3_region: Option<String>,
+    #[serde(default)]
+    pub s3_endpoint: Option<String>,
+    #[serde(default)]
+    pub s3_access_key_id: Option<String>,
+    #[serde(default)]
+    pub s3_secret_access_key: Option<String>,
+    #[serde(default)]
+    pub s3_prefix: Option<String>,
+    #[serde(default)]
+    pub s3_presign_ttl: Option<u64>,
+    #[serde(default)]
+    pub download_mode: DownloadMode,
+    /// Seconds a metadata request to an upstream API may take.
+    #[serde(default = "default_http_timeout")]
+    pub http_timeout: u64,
+    #[serde(default)]
+    pub games: HashMap<String, GameSettings>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub loaders: HashMap<String, LoaderSettings>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoaderSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub metadata_ttl: Option<u64>,
+    #[serde(default)]
+    pub http_timeout: Option<u64>,
+    /// Bounds on the loader's share of the cache, within `cache_max_bytes` and
+    /// `cache_max_entries`.
+    #[serde(default)]
+    pub cache_max_bytes: Option<u64>,
+    #[serde(default)]
+    pub cache_max_entries: Option<u64>,
+    /// Upstream base URLs mapped to the mirrors that replace them, for both metadata
+    /// requests and downloads.
+    #[serde(default)]
+    pub mirrors: BTreeMap<String, String>,
+}
+
+/// Settings that are only read on startup, by key or key prefix.
+const STARTUP_ONLY: &[&str] = &[
+    "bind_address",
+    "storage_path",
+    "log_level",
+    "download_mode",
+    "storage_backend",
+    "s3_",
+];
+
+/// A setting that differs between two [`Settings`], by its dotted key. A `None` value is
+/// unset.
+pub struct SettingChange {
+    pub key: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl SettingChange {
+    /// Whether the change only takes effect after a restart.
+    pub fn needs_restart(&self) -> bool {
+        STARTUP_ONLY.iter().any(|key| self.key.starts_with(key))
+    }
+
+    fn is_secret(&self) -> bool {
+        self.key.contains("secret") || self.key.contains("access_key")
+    }
+}
+
+impl fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| match value {
+            None => "unset".to_string(),
+            Some(_) if self.is_secret() => "<redacted>".to_string(),
+            Some(value) => value.clone(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.key,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// What a registered loader is configured with.
+pub struct LoaderConfig {
+    pub metadata_ttl: Duration,
+    pub upstream: Upstream,
+}
 
 fn default_bind_address() -> String {
     "127.0.0.1:8080".to_string()
@@ -30,17 +156,340 @@ fn default_cache_ttl() -> u64 {
     3600
 }
 
+fn default_metadata_ttl() -> u64 {
+    300
+}
+
+fn default_storage_backend() -> String {
+    "local".to_string()
+}
+
+fn default_http_timeout() -> u64 {
+    30
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// The config file named by `--config <path>` or `--config=<path>`, or else by
+/// `WAREHOUSE_CONFIG`.
+pub fn config_path() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("WAREHOUSE_CONFIG").map(PathBuf::from)
+}
+
 impl Settings {
-    pub fn new() -> Result<Self, config::ConfigError> {
-        
+    pub fn new() -> anyhow::Result<Self> {
         dotenvy::dotenv().ok();
+        Self::load(config_path().as_deref())
+    }
+
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut builder = Config::builder();
+        if let Some(path) = path {
+            builder = builder.add_source(File::from(path));
+        }
 
-        
-        let config = Config::builder()
-            .add_source(Environment::with_prefix("WAREHOUSE"))
+        // The variable naming the config file is not a setting itself.
+        let environment = std::env::vars()
+            .filter(|(key, _)| key != "WAREHOUSE_CONFIG")
+            .collect();
+        let config = builder
+            .add_source(
+                Environment::with_prefix("WAREHOUSE")
+                    .prefix_separator("_")
+                    .separator("__")
+                    .source(Some(environment)),
+            )
             .build()?;
 
-        
-        Ok(config.try_deserialize()?)
+        let settings: Self = config.try_deserialize()?;
+        if settings.http_timeout == 0 {
+            bail!("http_timeout must be at least one second");
+        }
+        Ok(settings)
+    }
+
+    /// Checks that every game and loader the settings configure exists in `games`, and
+    /// that their values are usable.
+    pub fn validate(&self, games: &[Game]) -> anyhow::Result<()> {
+        for (game_id, game_settings) in &self.games {
+            let Some(game) = games.iter().find(|game| game.id() == game_id) else {
+                bail!("unknown game 'games.{}'", game_id);
+            };
+            for (loader, loader_settings) in &game_settings.loaders {
+                let key = format!("games.{}.loaders.{}", game_id, loader);
+                if game.get_loader(loader).is_none() {
+                    bail!("unknown loader '{}'", key);
+                }
+                if loader_settings.http_timeout == Some(0) {
+                    bail!("{}.http_timeout must be at least one second", key);
+                }
+                let quotas = [
+                    (
+                        "cache_max_bytes",
+                        loader_settings.cache_max_bytes,
+                        self.cache_max_bytes,
+                    ),
+                    (
+                        "cache_max_entries",
+                        loader_settings.cache_max_entries,
+                        self.cache_max_entries,
+                    ),
+                ];
+                for (name, limit, cache_limit) in quotas {
+                    if limit == Some(0) {
+                        bail!("{}.{} must be at least 1", key, name);
+                    }
+                    if let (Some(limit), Some(cache_limit)) = (limit, cache_limit) {
+                        if limit > cache_limit {
+                            bail!("{}.{} exceeds {} of {}", key, name, name, cache_limit);
+                        }
+                    }
+                }
+                for (base, mirror) in &loader_settings.mirrors {
+                    for url in [base, mirror] {
+                        reqwest::Url::parse(url)
+                            .with_context(|| format!("invalid URL '{}' in {}.mirrors", url, key))?;
+                    }
+                }
+            }
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Lists the settings that differ in `new`, by key.
+    pub fn diff(&self, new: &Settings) -> Vec<SettingChange> {
+        let (old, new) = (self.flatten(), new.flatten());
+        old.keys()
+            .chain(new.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|key| old.get(*key) != new.get(*key))
+            .map(|key| SettingChange {
+                key: key.clone(),
+                old: old.get(key).cloned(),
+                new: new.get(key).cloned(),
+            })
+            .collect()
+    }
+
+    /// Every set value by its dotted key, such as `games.minecraft.loaders.paper.enabled`.
+    fn flatten(&self) -> BTreeMap<String, String> {
+        fn walk(key: String, value: Value, flat: &mut BTreeMap<String, String>) {
+            match value {
+                Value::Object(fields) => {
+                    for (field, value) in fields {
+                        let key = if key.is_empty() {
+                            field
+                        } else {
+                            format!("{}.{}", key, field)
+                        };
+                        walk(key, value, flat);
+                    }
+                }
+                Value::Null => {}
+                value => {
+                    flat.insert(key, value.to_string());
+                }
+            }
+        }
+
+        let mut flat = BTreeMap::new();
+        walk(
+            String::new(),
+            serde_json::to_value(self).unwrap_or_default(),
+            &mut flat,
+        );
+        flat
+    }
+
+    /// The bounds on the whole cache.
+    pub fn cache_quota(&self) -> CacheQuota {
+        CacheQuota {
+            max_bytes: self.cache_max_bytes,
+            max_entries: self.cache_max_entries,
+        }
+    }
+
+    /// The bounds on the share of the cache of every loader that has any, by game and
+    /// loader.
+    pub fn loader_cache_quotas(&self) -> Vec<((String, String), CacheQuota)> {
+        let mut quotas = Vec::new();
+        for (game, game_settings) in &self.games {
+            for (loader, settings) in &game_settings.loaders {
+                let quota = CacheQuota {
+                    max_bytes: settings.cache_max_bytes,
+                    max_entries: settings.cache_max_entries,
+                };
+                if quota != CacheQuota::default() {
+                    quotas.push(((game.clone(), loader.clone()), quota));
+                }
+            }
+        }
+        quotas
+    }
+
+    pub fn game_enabled(&self, game: &str) -> bool {
+        self.games.get(game).is_none_or(|game| game.enabled)
+    }
+
+    /// The configuration of a loader, or `None` if it is disabled. A loader's own settings
+    /// take precedence over `loader_metadata_ttl`, which applies to loaders of that name in
+    /// every game, and over the global defaults.
+    pub fn loader(&self, game: &str, loader: &str) -> Option<LoaderConfig> {
+        let settings = self
+            .games
+            .get(game)
+            .and_then(|game| game.loaders.get(loader));
+        if settings.is_some_and(|settings| !settings.enabled) {
+            return None;
+        }
+
+        let metadata_ttl = settings
+            .and_then(|settings| settings.metadata_ttl)
+            .or_else(|| self.loader_metadata_ttl.get(loader).copied())
+            .unwrap_or(self.metadata_ttl);
+        let http_timeout = settings
+            .and_then(|settings| settings.http_timeout)
+            .unwrap_or(self.http_timeout);
+
+        Some(LoaderConfig {
+            metadata_ttl: Duration::from_secs(metadata_ttl),
+            upstream: Upstream {
+                timeout: Duration::from_secs(http_timeout),
+                mirrors: settings
+                    .map(|settings| settings.mirrors.clone())
+                    .unwrap_or_default(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::minecraft::minecraft;
+    use std::io::Write;
+
+    /// Loads settings from a TOML file with the given contents.
+    fn load(toml: &str) -> anyhow::Result<Settings> {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+        file.write_all(toml.as_bytes())?;
+        Settings::load(Some(file.path()))
+    }
+
+    fn validate(toml: &str) -> anyhow::Result<()> {
+        load(toml)?.validate(&[minecraft()])
+    }
+
+    fn error(result: anyhow::Result<()>) -> String {
+        format!("{:#}", result.unwrap_err())
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(load("cache_tll = 60").is_err());
+        assert!(load("[games.minecraft.loaders.paper]\nmetadata_tll = 60").is_err());
+        assert!(load("cache_ttl = 60").is_ok());
+    }
+
+    #[test]
+    fn prefers_environment_variables_over_the_file() {
+        // Tests share the environment, so no other test may depend on `log_level`.
+        std::env::set_var("WAREHOUSE_LOG_LEVEL", "debug");
+        let settings = load("log_level = \"warn\"\nmetadata_ttl = 120");
+        std::env::remove_var("WAREHOUSE_LOG_LEVEL");
+
+        let settings = settings.unwrap();
+        assert_eq!(settings.log_level, "debug");
+        assert_eq!(settings.metadata_ttl, 120);
+    }
+
+    #[test]
+    fn rejects_unknown_games_and_loaders() {
+        assert_eq!(
+            error(validate("[games.tetris]\nenabled = false")),
+            "unknown game 'games.tetris'"
+        );
+        assert_eq!(
+            error(validate(
+                "[games.minecraft.loaders.spigot]\nenabled = false"
+            )),
+            "unknown loader 'games.minecraft.loaders.spigot'"
+        );
+        assert!(validate("[games.minecraft.loaders.paper]\nenabled = false").is_ok());
+    }
+
+    #[test]
+    fn rejects_unusable_loader_settings() {
+        assert_eq!(
+            error(validate(
+                "[games.minecraft.loaders.paper]\nhttp_timeout = 0"
+            )),
+            "games.minecraft.loaders.paper.http_timeout must be at least one second"
+        );
+        assert!(error(validate(
+            "[games.minecraft.loaders.paper]\nmirrors = { \"https://api.papermc.io\" = \"mirror\" }"
+        ))
+        .starts_with("invalid URL 'mirror' in games.minecraft.loaders.paper.mirrors"));
+    }
+
+    #[test]
+    fn checks_loader_quotas_against_the_cache_quota() {
+        assert_eq!(
+            error(validate(
+                "[games.minecraft.loaders.paper]\ncache_max_entries = 0"
+            )),
+            "games.minecraft.loaders.paper.cache_max_entries must be at least 1"
+        );
+        assert_eq!(
+            error(validate(
+                "cache_max_bytes = 100\n[games.minecraft.loaders.paper]\ncache_max_bytes = 101"
+            )),
+            "games.minecraft.loaders.paper.cache_max_bytes exceeds cache_max_bytes of 100"
+        );
+        let settings =
+            load("cache_max_bytes = 100\n[games.minecraft.loaders.paper]\ncache_max_bytes = 50")
+                .unwrap();
+        assert!(settings.validate(&[minecraft()]).is_ok());
+        assert_eq!(
+            settings.loader_cache_quotas(),
+            [(
+                ("minecraft".to_string(), "paper".to_string()),
+                CacheQuota {
+                    max_bytes: Some(50),
+                    max_entries: None,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn lists_changed_settings_without_revealing_secrets() {
+        let old = load("cache_ttl = 60\ns3_secret_access_key = \"old\"").unwrap();
+        let new = load("cache_ttl = 120\ns3_secret_access_key = \"new\"").unwrap();
+
+        let changes = old.diff(&new);
+        let shown = changes.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            shown,
+            [
+                "cache_ttl: 60 -> 120",
+                "s3_secret_access_key: <redacted> -> <redacted>"
+            ]
+        );
+        assert!(!changes[0].needs_restart());
+        assert!(changes[1].needs_restart());
+        assert!(old.diff(&old).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use actix_web::http::StatusCode;
+use std::fmt;
+
+/// An error as reported to API clients, with an HTTP status and a stable machine-readable
+/// code. Loaders and the cache keep returning `anyhow` errors; the conversion from
+/// [`anyhow::Error`] finds a `WarehouseError` anywhere in the chain, and otherwise
+/// classifies the error by its cause.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarehouseError {
+    /// A query parameter is malformed.
+    BadRequest(String),
+    GameNotFound(String),
+    LoaderNotFound {
+        game: String,
+        loader: String,
+    },
+    VersionNotFound(String),
+    BuildNotFound(String),
+    /// An upstream API or download failed or answered with something unusable.
+    Upstream(String),
+    UpstreamTimeout(String),
+    /// A dependency of the service itself, such as the storage backend, is unavailable.
+    Unavailable(String),
+    Internal(String),
+}
+
+impl WarehouseError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::GameNotFound(_)
+            | Self::LoaderNotFound { .. }
+            | Self::VersionNotFound(_)
+            | Self::BuildNotFound(_) => StatusCode::NOT_FOUND,
+            Self::Upstream(_) => StatusCode::BAD_GATEWAY,
+            Self::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// A code that stays the same across releases, for clients to branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "bad_request",
+            Self::GameNotFound(_) => "game_not_found",
+            Self::LoaderNotFound { .. } => "loader_not_found",
+            Self::VersionNotFound(_) => "version_not_found",
+            Self::BuildNotFound(_) => "build_not_found",
+            Self::Upstream(_) => "upstream_error",
+            Self::UpstreamTimeout(_) => "upstream_timeout",
+            Self::Unavailable(_) => "unavailable",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for WarehouseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadRequest(msg) => write!(f, "{}", msg),
+            Self::GameNotFound(game) => write!(f, "Game '{}' not found", game),
+            Self::LoaderNotFound { game, loader } => {
+                write!(f, "Loader '{}' not found for game '{}'", loader, game)
+            }
+            Self::VersionNotFound(version) => write!(f, "Version '{}' not found", version),
+            Self::BuildNotFound(msg) => write!(f, "{}", msg),
+            Self::Upstream(msg) => write!(f, "Upstream request failed: {}", msg),
+            Self::UpstreamTimeout(msg) => write!(f, "Upstream request timed out: {}", msg),
+            Self::Unavailable(msg) => write!(f, "Service unavailable: {}", msg),
+            Self::Internal(msg) => write!(f, "Internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WarehouseError {}
+
+impl From<&anyhow::Error> for WarehouseError {
+    fn from(error: &anyhow::Error) -> Self {
+        let message = format!("{:#}", error);
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<WarehouseError>() {
+                return error.clone();
+            }
+            if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+                return if error.is_timeout() {
+                    Self::UpstreamTimeout(message)
+                } else {
+                    Self::Upstream(message)
+                };
+            }
+            if cause.is::<object_store::Error>() {
+                return Self::Unavailable(message);
+            }
+        }
+        Self::Internal(message)
+    }
+}
+
+impl From<anyhow::Error> for WarehouseError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::from(&error)
+    }
+}
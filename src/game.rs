@@ -1,45 +1,182 @@
-use crate::cache::CacheManager;
+use crate::cache::{CacheManager, CacheWriter, CachedFile};
+use crate::checksum::{ChecksumHasher, Checksums};
+use crate::config::Settings;
+use crate::error::WarehouseError;
+use crate::games::common::HttpClient;
+use crate::metadata::{CachedLoader, LoaderHealth};
+use crate::metrics::InFlight;
+use crate::singleflight::{self, Flight, FlightGuard, SingleFlight};
+use crate::storage::{self, ByteStream};
+use crate::version_order::VersionRange;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tracing::{info, warn};
+
+/// The release channel a version was published on.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum VersionType {
+    Release,
+    Snapshot,
+    PreRelease,
+    ReleaseCandidate,
+    Experimental,
+    OldBeta,
+    OldAlpha,
+    /// A channel reported by an upstream that warehouse does not know about, kept verbatim.
+    Other(String),
+}
+
+impl VersionType {
+    /// Classifies a Minecraft version id that an upstream only labels as "snapshot".
+    pub fn from_snapshot_id(id: &str) -> Self {
+        let id = id.to_ascii_lowercase();
+        if id.contains("experimental") {
+            Self::Experimental
+        } else if id.contains("-rc") || id.contains(" release candidate") {
+            Self::ReleaseCandidate
+        } else if id.contains("-pre") || id.contains(" pre-release") {
+            Self::PreRelease
+        } else {
+            Self::Snapshot
+        }
+    }
+
+    /// Classifies a Minecraft version id for upstreams that publish no channel at all.
+    pub fn from_id(id: &str) -> Self {
+        if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            Self::Release
+        } else {
+            Self::from_snapshot_id(id)
+        }
+    }
+
+    pub fn is_release(&self) -> bool {
+        *self == Self::Release
+    }
+}
+
+impl From<&str> for VersionType {
+    fn from(value: &str) -> Self {
+        match value {
+            "release" => Self::Release,
+            "snapshot" => Self::Snapshot,
+            "pre_release" => Self::PreRelease,
+            "release_candidate" => Self::ReleaseCandidate,
+            "experimental" => Self::Experimental,
+            "old_beta" => Self::OldBeta,
+            "old_alpha" => Self::OldAlpha,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for VersionType {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl From<VersionType> for String {
+    fn from(value: VersionType) -> Self {
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for VersionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Release => write!(f, "release"),
+            Self::Snapshot => write!(f, "snapshot"),
+            Self::PreRelease => write!(f, "pre_release"),
+            Self::ReleaseCandidate => write!(f, "release_candidate"),
+            Self::Experimental => write!(f, "experimental"),
+            Self::OldBeta => write!(f, "old_beta"),
+            Self::OldAlpha => write!(f, "old_alpha"),
+            Self::Other(other) => write!(f, "{}", other),
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Version {
     pub id: String,
     #[serde(rename = "type")]
-    pub version_type: String,
+    pub version_type: VersionType,
     #[serde(default)]
     pub is_stable: bool,
+    #[serde(default)]
+    pub release_time: Option<DateTime<Utc>>,
 }
 
 impl Version {
-    pub fn new(id: String, version_type: String, is_stable: bool) -> Self {
+    pub fn new(id: String, version_type: VersionType, is_stable: bool) -> Self {
         Self {
             id,
             version_type,
             is_stable,
+            release_time: None,
         }
     }
 
-    pub fn new_standard(id: String, version_type: String) -> Self {
-        Self {
-            id,
-            version_type: version_type.clone(),
-            is_stable: version_type == "release",
-        }
+    pub fn new_standard(id: String, version_type: VersionType) -> Self {
+        let is_stable = version_type.is_release();
+        Self::new(id, version_type, is_stable)
+    }
+
+    pub fn with_release_time(mut self, release_time: DateTime<Utc>) -> Self {
+        self.release_time = Some(release_time);
+        self
     }
 
     pub fn id(&self) -> &str {
         &self.id
     }
-    pub fn version_type(&self) -> &str {
+    pub fn version_type(&self) -> &VersionType {
         &self.version_type
     }
     pub fn is_stable(&self) -> bool {
         self.is_stable
     }
+    pub fn release_time(&self) -> Option<DateTime<Utc>> {
+        self.release_time
+    }
+}
+
+/// What kind of file a build's download is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// A server jar that can be run directly.
+    #[default]
+    Server,
+    /// An installer jar that must be run to produce the server before it can be started.
+    Installer,
+}
+
+impl std::fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Server => write!(f, "server"),
+            Self::Installer => write!(f, "installer"),
+        }
+    }
+}
+
+/// An entry in a build's changelog.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub commit: Option<String>,
+    pub summary: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -47,6 +184,28 @@ pub struct Build {
     id: String,
     version: Version,
     download_url: Option<String>,
+    #[serde(default = "default_build_stable")]
+    is_stable: bool,
+    #[serde(default)]
+    artifact: ArtifactKind,
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    checksums: Checksums,
+    #[serde(default)]
+    release_time: Option<DateTime<Utc>>,
+    /// The major Java version the build needs to run.
+    #[serde(default)]
+    java_version: Option<u32>,
+    /// The size of the download in bytes.
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    changes: Vec<Change>,
+}
+
+fn default_build_stable() -> bool {
+    true
 }
 
 impl Build {
@@ -55,15 +214,74 @@ impl Build {
             id,
             version,
             download_url,
+            is_stable: true,
+            artifact: ArtifactKind::Server,
+            filename: None,
+            checksums: Checksums::default(),
+            release_time: None,
+            java_version: None,
+            size: None,
+            changes: Vec::new(),
         }
     }
 
     pub fn new_standard(id: String, version: Version, download_url: Option<String>) -> Self {
-        Self {
-            id,
-            version,
-            download_url,
-        }
+        Self::new(id, version, download_url)
+    }
+
+    pub fn with_download_url(mut self, download_url: impl Into<String>) -> Self {
+        self.download_url = Some(download_url.into());
+        self
+    }
+
+    pub fn with_stable(mut self, is_stable: bool) -> Self {
+        self.is_stable = is_stable;
+        self
+    }
+
+    pub fn with_artifact(mut self, artifact: ArtifactKind) -> Self {
+        self.artifact = artifact;
+        self
+    }
+
+    pub fn with_filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn with_sha1(mut self, sha1: impl Into<String>) -> Self {
+        self.checksums.sha1 = Some(sha1.into());
+        self
+    }
+
+    pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.checksums.sha256 = Some(sha256.into());
+        self
+    }
+
+    pub fn with_md5(mut self, md5: impl Into<String>) -> Self {
+        self.checksums.md5 = Some(md5.into());
+        self
+    }
+
+    pub fn with_release_time(mut self, release_time: DateTime<Utc>) -> Self {
+        self.release_time = Some(release_time);
+        self
+    }
+
+    pub fn with_java_version(mut self, java_version: u32) -> Self {
+        self.java_version = Some(java_version);
+        self
+    }
+
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_changes(mut self, changes: Vec<Change>) -> Self {
+        self.changes = changes;
+        self
     }
 
     pub fn id(&self) -> &str {
@@ -75,27 +293,211 @@ impl Build {
     pub fn download_url(&self) -> Option<&str> {
         self.download_url.as_deref()
     }
+    pub fn is_stable(&self) -> bool {
+        self.is_stable
+    }
+    pub fn artifact(&self) -> ArtifactKind {
+        self.artifact
+    }
+    pub fn checksums(&self) -> &Checksums {
+        &self.checksums
+    }
+    pub fn release_time(&self) -> Option<DateTime<Utc>> {
+        self.release_time
+    }
+    pub fn java_version(&self) -> Option<u32> {
+        self.java_version
+    }
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
     pub fn filename(&self) -> String {
-        format!("{}-{}.jar", self.version.id, self.id)
+        match &self.filename {
+            Some(filename) => filename.clone(),
+            None => match self.artifact {
+                ArtifactKind::Server => format!("{}-{}.jar", self.version.id, self.id),
+                ArtifactKind::Installer => {
+                    format!("{}-{}-installer.jar", self.version.id, self.id)
+                }
+            },
+        }
+    }
+}
+
+/// Extra coordinates a loader may need to produce a build, beyond the game version.
+#[derive(Clone, Debug, Default)]
+pub struct BuildOptions {
+    pub installer: Option<String>,
+}
+
+impl BuildOptions {
+    /// Options as requested by a client. The installer goes into upstream URLs, file names
+    /// and cache keys, so it must look like a version ID; loaders check that it exists.
+    pub fn new(installer: Option<String>) -> Result<Self, WarehouseError> {
+        if let Some(installer) = &installer {
+            if !is_version_id(installer) {
+                return Err(WarehouseError::BadRequest(format!(
+                    "installer '{}' is not a version",
+                    installer
+                )));
+            }
+        }
+        Ok(Self { installer })
+    }
+}
+
+fn is_version_id(id: &str) -> bool {
+    id.len() <= 64
+        && id.starts_with(|c: char| c.is_ascii_digit())
+        && !id.contains("..")
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'+' | b'_'))
+}
+
+/// A version requested either by ID or by one of the `latest`, `latest-stable` and
+/// `latest-snapshot` aliases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionSelector {
+    Latest,
+    LatestStable,
+    LatestSnapshot,
+    Exact(String),
+}
+
+impl From<&str> for VersionSelector {
+    fn from(value: &str) -> Self {
+        match value {
+            "latest" => VersionSelector::Latest,
+            "latest-stable" => VersionSelector::LatestStable,
+            "latest-snapshot" => VersionSelector::LatestSnapshot,
+            id => VersionSelector::Exact(id.to_string()),
+        }
+    }
+}
+
+/// A build requested either by ID or by one of the `latest` and `latest-stable` aliases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildSelector {
+    Latest,
+    LatestStable,
+    Exact(String),
+}
+
+impl From<&str> for BuildSelector {
+    fn from(value: &str) -> Self {
+        match value {
+            "latest" => BuildSelector::Latest,
+            "latest-stable" => BuildSelector::LatestStable,
+            id => BuildSelector::Exact(id.to_string()),
+        }
+    }
+}
+
+/// Orders build IDs so that later builds compare greater. Runs of digits are compared as
+/// numbers, and an ID with a `-` suffix such as `-beta.1` sorts before the same ID without.
+pub fn compare_build_ids(a: &str, b: &str) -> Ordering {
+    let (a_base, a_suffix) = split_suffix(a);
+    let (b_base, b_suffix) = split_suffix(b);
+
+    natural_cmp(a_base, b_base).then_with(|| match (a_suffix, b_suffix) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => natural_cmp(a, b),
+    })
+}
+
+fn split_suffix(id: &str) -> (&str, Option<&str>) {
+    match id.split_once('-') {
+        Some((base, suffix)) => (base, Some(suffix)),
+        None => (id, None),
+    }
+}
+
+/// Compares strings with runs of digits compared as numbers, so that `beta10` sorts after
+/// `beta9`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = chunks(a);
+    let mut b = chunks(b);
+
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) if is_numeric(a) && is_numeric(b) => {
+                let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            (Some(a), Some(b)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
     }
 }
 
+/// Splits a string into alternating runs of digits and non-digits.
+fn chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+fn is_numeric(chunk: &str) -> bool {
+    chunk.starts_with(|c: char| c.is_ascii_digit())
+}
+
 #[async_trait::async_trait]
 pub trait GameLoader: Send + Sync + std::fmt::Debug + 'static {
     fn name(&self) -> &str;
     fn website(&self) -> Option<&str>;
 
-    fn supports_version_type(&self, _version_type: &str) -> bool {
+    fn supports_version_type(&self, _version_type: &VersionType) -> bool {
         true
     }
 
     async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>>;
     async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>>;
 
+    async fn fetch_builds_with(
+        &self,
+        version: &Version,
+        _options: &BuildOptions,
+    ) -> anyhow::Result<Vec<Build>> {
+        self.fetch_builds(version).await
+    }
+
+    async fn get_version(&self, id: &str) -> anyhow::Result<Option<Version>> {
+        let versions = self.fetch_versions().await?;
+        Ok(versions.into_iter().find(|v| v.id() == id))
+    }
+
     async fn get_latest_stable(&self) -> anyhow::Result<Option<Version>> {
         let versions = self.fetch_versions().await?;
         Ok(versions.into_iter().find(|v| v.is_stable()))
     }
+
+    /// The client the loader reaches its upstream with, so that it can be configured.
+    fn http_client(&self) -> Option<&HttpClient> {
+        None
+    }
+
+    /// The state of the loader's upstream, for loaders that track it.
+    fn health(&self) -> Option<LoaderHealth> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -130,53 +532,434 @@ impl Game {
     }
 }
 
+/// Builds the registry of enabled games and loaders from `catalog`, configuring each
+/// loader's upstream and putting it behind a metadata cache. Given the settings and
+/// registry being replaced, loaders whose metadata TTL did not change are carried over.
+fn configure_games(
+    catalog: &[Game],
+    settings: &Settings,
+    previous: Option<(&Settings, &HashMap<String, Game>)>,
+) -> HashMap<String, Game> {
+    let mut registry = HashMap::new();
+    for game in catalog {
+        if !settings.game_enabled(game.id()) {
+            info!("game {} is disabled", game.id());
+            continue;
+        }
+
+        let mut loaders = HashMap::new();
+        for (name, loader) in &game.loaders {
+            let Some(config) = settings.loader(game.id(), name) else {
+                info!("loader {}/{} is disabled", game.id(), name);
+                continue;
+            };
+            if let Some(client) = loader.http_client() {
+                client.configure(config.upstream);
+            }
+
+            let kept = previous.and_then(|(previous_settings, previous_registry)| {
+                let previous_config = previous_settings.loader(game.id(), name)?;
+                (previous_config.metadata_ttl == config.metadata_ttl)
+                    .then(|| previous_registry.get(game.id())?.get_loader(name))
+                    .flatten()
+            });
+            let loader = kept.unwrap_or_else(|| {
+                Arc::new(CachedLoader::new(loader.clone(), config.metadata_ttl))
+            });
+            loaders.insert(name.clone(), loader);
+        }
+
+        registry.insert(
+            game.id().to_string(),
+            Game {
+                id: game.id().to_string(),
+                loaders,
+            },
+        );
+    }
+    registry
+}
+
+/// How a build is delivered to the client.
+pub enum Download {
+    /// A verified entry in the cache.
+    Cached(CachedFile),
+    /// Bytes streamed from upstream while they are written to the cache, with the length
+    /// upstream announced.
+    Stream { body: ByteStream, size: Option<u64> },
+    /// A URL the client can fetch the build from directly.
+    Redirect(String),
+}
+
+/// How many chunks a client streaming a download from upstream may fall behind before it
+/// is cut off. One more slot is kept free to tell it why.
+const DOWNLOAD_CHANNEL_SIZE: usize = 256;
+
+/// Code holding more than one of `settings`, `games` and `catalog` at once acquires them
+/// in that order, so that concurrent registrations and reloads cannot deadlock.
 #[derive(Clone, Debug)]
 pub struct GameProvider {
     games: Arc<RwLock<HashMap<String, Game>>>,
     pub cache: Arc<CacheManager>,
+    downloads: Arc<SingleFlight<DownloadKey, CachedFile>>,
+    presign_ttl: Option<Duration>,
+    /// Every game that can be served, with its loaders as they are before configuration.
+    catalog: Arc<RwLock<Vec<Game>>>,
+    settings: Arc<RwLock<Settings>>,
 }
 
+/// In-flight downloads are keyed by game, loader and cache file name.
+type DownloadKey = (String, String, String);
+
+/// The data of a download as it arrives from upstream, with the length upstream announced.
+type StartedDownload = (mpsc::Receiver<io::Result<Bytes>>, Option<u64>);
+
 impl GameProvider {
-    pub fn from_settings(settings: &crate::config::Settings) -> Self {
-        Self {
+    pub fn from_settings(settings: &Settings) -> anyhow::Result<Self> {
+        let cache = CacheManager::new(
+            PathBuf::from(settings.storage_path.clone()),
+            storage::from_settings(settings)?,
+            settings.cache_ttl,
+            settings.cache_quota(),
+        )?;
+        cache.set_loader_quotas(settings.loader_cache_quotas());
+
+        Ok(Self {
             games: Arc::new(RwLock::new(HashMap::new())),
-            cache: Arc::new(CacheManager::new(
-                PathBuf::from(settings.storage_path.clone()),
-                settings.cache_ttl,
-            )),
-        }
+            cache: Arc::new(cache),
+            downloads: Arc::new(SingleFlight::default()),
+            presign_ttl: settings.s3_presign_ttl.map(Duration::from_secs),
+            catalog: Arc::new(RwLock::new(Vec::new())),
+            settings: Arc::new(RwLock::new(settings.clone())),
+        })
+    }
+
+    /// Registers every game warehouse can serve, after checking that the settings only
+    /// configure games and loaders among them.
+    pub async fn register_games(&self, games: Vec<Game>) -> anyhow::Result<()> {
+        let settings = self.settings.read().await;
+        settings.validate(&games)?;
+
+        let mut registry = self.games.write().await;
+        let mut catalog = self.catalog.write().await;
+        *registry = configure_games(&games, &settings, None);
+        *catalog = games;
+        Ok(())
+    }
+
+    /// Applies new settings to the registered games and the cache. Loaders whose metadata
+    /// TTL is unchanged keep their cached metadata. Settings that only take effect on
+    /// startup are stored but not applied. Nothing changes if the settings are invalid.
+    pub async fn reconfigure(&self, settings: Settings) -> anyhow::Result<()> {
+        let mut current = self.settings.write().await;
+        let mut registry = self.games.write().await;
+        let catalog = self.catalog.read().await;
+        settings.validate(&catalog)?;
+
+        *registry = configure_games(&catalog, &settings, Some((&current, &registry)));
+        self.cache
+            .set_limits(settings.cache_ttl, settings.cache_quota());
+        self.cache.set_loader_quotas(settings.loader_cache_quotas());
+        *current = settings;
+        Ok(())
     }
 
-    pub async fn register_game(&self, game: Game) {
-        let mut games = self.games.write().await;
-        games.insert(game.id().to_string(), game);
+    pub async fn settings(&self) -> Settings {
+        self.settings.read().await.clone()
     }
 
     pub async fn get_game(&self, name: &str) -> Option<Game> {
         self.games.read().await.get(name).cloned()
     }
 
-    pub async fn get_loader(&self, game_id: &str, loader_id: &str) -> Option<Arc<dyn GameLoader>> {
-        self.get_game(game_id).await?.get_loader(loader_id)
+    pub async fn get_loader(
+        &self,
+        game_id: &str,
+        loader_id: &str,
+    ) -> Result<Arc<dyn GameLoader>, WarehouseError> {
+        let game = self
+            .get_game(game_id)
+            .await
+            .ok_or_else(|| WarehouseError::GameNotFound(game_id.to_string()))?;
+        game.get_loader(loader_id)
+            .ok_or_else(|| WarehouseError::LoaderNotFound {
+                game: game_id.to_string(),
+                loader: loader_id.to_string(),
+            })
+    }
+
+    /// Resolves a version ID or alias. Loaders list versions newest first, so `latest` and
+    /// `latest-snapshot` take the first matching one. Within a `range`, aliases instead
+    /// take the highest matching version by [`Version::sort_key`], and an exact version
+    /// must match the range.
+    pub async fn resolve_version(
+        &self,
+        loader: &dyn GameLoader,
+        selector: &VersionSelector,
+        range: Option<&VersionRange>,
+    ) -> Result<Option<Version>, WarehouseError> {
+        let Some(range) = range else {
+            return match selector {
+                VersionSelector::Latest => Ok(loader.fetch_versions().await?.into_iter().next()),
+                VersionSelector::LatestStable => Ok(loader.get_latest_stable().await?),
+                VersionSelector::LatestSnapshot => Ok(loader
+                    .fetch_versions()
+                    .await?
+                    .into_iter()
+                    .find(|v| *v.version_type() == VersionType::Snapshot)),
+                VersionSelector::Exact(id) => Ok(loader.get_version(id).await?),
+            };
+        };
+
+        let mut versions = loader
+            .fetch_versions()
+            .await?
+            .into_iter()
+            .filter(|v| range.matches(v));
+        Ok(match selector {
+            VersionSelector::Latest => versions.max_by_key(Version::sort_key),
+            VersionSelector::LatestStable => versions
+                .filter(|v| v.is_stable())
+                .max_by_key(Version::sort_key),
+            VersionSelector::LatestSnapshot => versions
+                .filter(|v| *v.version_type() == VersionType::Snapshot)
+                .max_by_key(Version::sort_key),
+            VersionSelector::Exact(id) => versions.find(|v| v.id() == id),
+        })
+    }
+
+    /// Picks a build by ID or alias after ordering `builds` newest first. Without a
+    /// selector the newest stable build is preferred, falling back to the newest build of
+    /// any kind.
+    pub fn resolve_build(
+        &self,
+        mut builds: Vec<Build>,
+        selector: Option<&BuildSelector>,
+    ) -> Option<Build> {
+        builds.sort_by(|a, b| compare_build_ids(b.id(), a.id()));
+
+        match selector {
+            Some(BuildSelector::Latest) => builds.into_iter().next(),
+            Some(BuildSelector::LatestStable) => builds.into_iter().find(|b| b.is_stable()),
+            Some(BuildSelector::Exact(id)) => builds.into_iter().find(|b| b.id() == id),
+            None => {
+                let stable = builds.iter().position(|b| b.is_stable()).unwrap_or(0);
+                builds.into_iter().nth(stable)
+            }
+        }
     }
 
-    pub async fn download_build(&self, game_name: &str, build: &Build) -> anyhow::Result<Vec<u8>> {
+    pub async fn download_build(
+        &self,
+        game_name: &str,
+        loader_name: &str,
+        build: &Build,
+    ) -> Result<Download, WarehouseError> {
+        if let Some(download) = self.cached_download(game_name, loader_name, build).await? {
+            return Ok(download);
+        }
         let filename = build.filename();
+        if build
+            .size()
+            .is_some_and(|size| !self.cache.fits(game_name, loader_name, size))
+        {
+            // Never cached, so there is no transfer worth waiting for.
+            info!(
+                "streaming {} without caching it, as it exceeds a cache quota",
+                filename
+            );
+            return self
+                .stream_upstream(game_name, loader_name, build, None)
+                .await;
+        }
+
+        // Only one request fetches a given build from upstream; concurrent requests for
+        // it wait for that transfer to land in the cache and are served from there. The
+        // transfer runs in its own task, so that it completes for them and for the cache
+        // even when the request that started it goes away, and it never waits for that
+        // request's client to read (see `ClientStream`).
+        let key = (
+            game_name.to_string(),
+            loader_name.to_string(),
+            filename.clone(),
+        );
+        let guard = match self.downloads.begin(key) {
+            Flight::Leader(guard) => guard,
+            Flight::Follower(waiter) => {
+                return Ok(self.serve_cached(singleflight::wait(waiter).await?).await?);
+            }
+        };
+        self.stream_upstream(game_name, loader_name, build, Some(guard))
+            .await
+    }
+
+    /// Fetches `build` from upstream in a task of its own and streams it to the caller,
+    /// teeing it into the cache for the flight `guard` leads, if any.
+    async fn stream_upstream(
+        &self,
+        game_name: &str,
+        loader_name: &str,
+        build: &Build,
+        guard: Option<FlightGuard<DownloadKey, CachedFile>>,
+    ) -> Result<Download, WarehouseError> {
+        let (started_tx, started_rx) = oneshot::channel();
+        tokio::spawn(self.clone().fetch_upstream(
+            game_name.to_string(),
+            loader_name.to_string(),
+            build.clone(),
+            guard,
+            started_tx,
+        ));
+        let (rx, size) = started_rx.await.map_err(|_| {
+            WarehouseError::Internal("download task ended unexpectedly".to_string())
+        })??;
+
+        Ok(Download::Stream {
+            body: Box::pin(stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|chunk| (chunk, rx))
+            })),
+            size,
+        })
+    }
+
+    /// Starts fetching `build` from upstream, reports the stream of its data through
+    /// `started` and, when there is a flight `guard`, tees it into the cache.
+    async fn fetch_upstream(
+        self,
+        game_name: String,
+        loader_name: String,
+        build: Build,
+        guard: Option<FlightGuard<DownloadKey, CachedFile>>,
+        started: oneshot::Sender<Result<StartedDownload, WarehouseError>>,
+    ) {
+        let cache = guard.is_some();
+        let (response, writer) = match self
+            .start_download(&game_name, &loader_name, &build, cache)
+            .await
+        {
+            Ok(started) => started,
+            Err(e) => {
+                let error = WarehouseError::from(&e);
+                if let Some(guard) = guard {
+                    guard.finish(Err(e)).ok();
+                }
+                started.send(Err(error)).ok();
+                return;
+            }
+        };
+
+        let size = response.content_length();
+        let (tx, rx) = mpsc::channel(DOWNLOAD_CHANNEL_SIZE + 1);
+        started.send(Ok((rx, size))).ok();
+        let client = ClientStream { tx: Some(tx) };
+        tee_download(response, writer, build.checksums().clone(), client, guard).await;
+    }
+
+    /// Serves the build from the cache if it is there, without fetching it from upstream.
+    pub async fn cached_download(
+        &self,
+        game_name: &str,
+        loader_name: &str,
+        build: &Build,
+    ) -> Result<Option<Download>, WarehouseError> {
+        match self
+            .cache
+            .get(game_name, loader_name, &build.filename(), build.checksums())
+            .await?
+        {
+            Some(cached) => Ok(Some(self.serve_cached(cached).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Redirects to cache entries when presigned URLs are enabled and the storage backend
+    /// supports them, and serves them through otherwise.
+    async fn serve_cached(&self, cached: CachedFile) -> anyhow::Result<Download> {
+        if let Some(ttl) = self.presign_ttl {
+            if let Some(url) = self.cache.presign(&cached.key, ttl).await? {
+                return Ok(Download::Redirect(url));
+            }
+        }
+
+        Ok(Download::Cached(cached))
+    }
+
+    /// Requests `build` from upstream and, if `cache` is set and its announced length fits
+    /// the cache, opens the cache entry to write it to.
+    async fn start_download(
+        &self,
+        game_name: &str,
+        loader_name: &str,
+        build: &Build,
+        cache: bool,
+    ) -> anyhow::Result<(reqwest::Response, Option<CacheWriter>)> {
+        let url = build.download_url().ok_or_else(|| {
+            WarehouseError::Upstream(format!("no download URL for build '{}'", build.id()))
+        })?;
 
-        if let Some(data) = self.cache.get(game_name, &filename).await? {
-            return Ok(data);
+        let response = reqwest::get(url).await?.error_for_status()?;
+        if !cache {
+            return Ok((response, None));
         }
+        if let Some(size) = response
+            .content_length()
+            .filter(|size| !self.cache.fits(game_name, loader_name, *size))
+        {
+            warn!(
+                "not caching {}: its {} bytes exceed a cache quota",
+                build.filename(),
+                size
+            );
+            return Ok((response, None));
+        }
+
+        let writer = self
+            .cache
+            .writer(
+                game_name,
+                loader_name,
+                &build.filename(),
+                build.checksums(),
+                Some(url),
+            )
+            .await?;
+        Ok((response, Some(writer)))
+    }
 
-        let url = build
-            .download_url()
-            .ok_or_else(|| anyhow::anyhow!("Download URL not available"))?;
+    /// Pins the latest stable build of every loader so that the cache never evicts it.
+    pub async fn refresh_pinned_builds(&self) {
+        let mut pinned = Vec::new();
 
-        let response = reqwest::get(url).await?;
-        let data = response.bytes().await?.to_vec();
+        for game in self.list_games().await {
+            for loader in game.list_loaders() {
+                let build = async {
+                    let Some(version) = self
+                        .resolve_version(loader.as_ref(), &VersionSelector::LatestStable, None)
+                        .await?
+                    else {
+                        return anyhow::Ok(None);
+                    };
+                    let builds = loader.fetch_builds(&version).await?;
+                    Ok(self.resolve_build(builds, Some(&BuildSelector::LatestStable)))
+                };
 
-        self.cache.put(game_name, &filename, &data, None).await?;
+                match build.await {
+                    Ok(Some(build)) => pinned.push((
+                        game.id().to_string(),
+                        loader.name().to_string(),
+                        build.filename(),
+                    )),
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        "failed to resolve latest stable {} build: {:#}",
+                        loader.name(),
+                        e
+                    ),
+                }
+            }
+        }
 
-        Ok(data)
+        self.cache.set_pinned(pinned);
     }
 
     pub async fn cleanup_cache(&self) -> anyhow::Result<()> {
@@ -187,3 +970,208 @@ impl GameProvider {
         self.games.read().await.values().cloned().collect()
     }
 }
+
+/// The requesting client's end of a download streamed from upstream. Sending never waits
+/// for it, so that the cache and the requests waiting for it do not depend on how fast
+/// this one client reads: a client that falls too far behind is cut off with an error.
+struct ClientStream {
+    tx: Option<mpsc::Sender<io::Result<Bytes>>>,
+}
+
+impl ClientStream {
+    fn connected(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    fn send(&mut self, chunk: Bytes) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        // The last slot is kept for the error.
+        if tx.capacity() <= 1 {
+            warn!("client fell behind the upstream download, ending its response");
+            self.fail(io::Error::other("client fell behind the download"));
+        } else if tx.try_send(Ok(chunk)).is_err() {
+            self.tx = None;
+        }
+    }
+
+    fn fail(&mut self, error: io::Error) {
+        if let Some(tx) = self.tx.take() {
+            tx.try_send(Err(error)).ok();
+        }
+    }
+}
+
+async fn tee_download(
+    response: reqwest::Response,
+    mut writer: Option<CacheWriter>,
+    expected: Checksums,
+    mut client: ClientStream,
+    guard: Option<FlightGuard<DownloadKey, CachedFile>>,
+) {
+    let _in_flight = InFlight::start();
+    let mut upstream = response.bytes_stream();
+    let mut hasher = ChecksumHasher::new(&expected);
+    // The last chunk is held back until the digests are verified, so that a client never
+    // receives a complete corrupted artifact.
+    let mut held: Option<Bytes> = None;
+
+    while let Some(chunk) = upstream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                client.fail(io::Error::other(e.to_string()));
+                finish(guard, Err(e.into()));
+                return;
+            }
+        };
+
+        hasher.update(&chunk);
+        if let Some(w) = &mut writer {
+            if let Err(e) = w.write(&chunk).await {
+                warn!("failed to write cache entry, serving uncached: {}", e);
+                writer = None;
+            }
+        }
+
+        if let Some(previous) = held.replace(chunk) {
+            client.send(previous);
+        }
+        if !client.connected() && writer.is_none() {
+            finish(guard, Err(anyhow::anyhow!("download could not be cached")));
+            return;
+        }
+    }
+
+    let checksums = match hasher.finish() {
+        Ok(checksums) => checksums,
+        Err(e) => {
+            warn!("discarding download: {}", e);
+            client.fail(io::Error::other(e.to_string()));
+            finish(guard, Err(e));
+            return;
+        }
+    };
+    if let Some(last) = held {
+        client.send(last);
+    }
+
+    match writer {
+        Some(writer) => {
+            let committed = writer.commit(checksums).await;
+            if let Err(e) = &committed {
+                warn!("failed to commit cache entry: {}", e);
+            }
+            finish(guard, committed);
+        }
+        None => finish(guard, Err(anyhow::anyhow!("download could not be cached"))),
+    }
+}
+
+/// Hands the outcome of a download to the requests waiting for it, if there are any.
+fn finish(guard: Option<FlightGuard<DownloadKey, CachedFile>>, result: anyhow::Result<CachedFile>) {
+    if let Some(guard) = guard {
+        guard.finish(result).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cuts_off_clients_that_fall_behind() {
+        let (tx, mut rx) = mpsc::channel(3);
+        let mut client = ClientStream { tx: Some(tx) };
+        for _ in 0..3 {
+            client.send(Bytes::from_static(b"chunk"));
+        }
+        assert!(!client.connected());
+
+        assert!(rx.recv().await.unwrap().is_ok());
+        assert!(rx.recv().await.unwrap().is_ok());
+        assert!(rx.recv().await.unwrap().is_err());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_streaming_to_clients_that_keep_up() {
+        let (tx, mut rx) = mpsc::channel(3);
+        let mut client = ClientStream { tx: Some(tx) };
+        for _ in 0..10 {
+            client.send(Bytes::from_static(b"chunk"));
+            assert_eq!(rx.recv().await.unwrap().unwrap(), "chunk");
+        }
+        assert!(client.connected());
+
+        drop(rx);
+        client.send(Bytes::from_static(b"chunk"));
+        assert!(!client.connected());
+    }
+
+    #[test]
+    fn accepts_only_version_like_installers() {
+        for installer in ["1.0.1", "0.11.2", "1.0.0-beta.3", "1.2+build.4"] {
+            let options = BuildOptions::new(Some(installer.to_string())).unwrap();
+            assert_eq!(options.installer.as_deref(), Some(installer));
+        }
+        assert!(BuildOptions::new(None).unwrap().installer.is_none());
+
+        let long = "1".repeat(65);
+        for installer in [
+            "",
+            "latest",
+            "1.0/../2",
+            "1..0",
+            "1.0 ",
+            "1.0%2F",
+            long.as_str(),
+        ] {
+            let err = BuildOptions::new(Some(installer.to_string())).unwrap_err();
+            assert_eq!(err.code(), "bad_request", "accepted {:?}", installer);
+        }
+    }
+
+    #[test]
+    fn compares_digit_runs_as_numbers() {
+        assert_eq!(compare_build_ids("47.10.0", "47.9.0"), Ordering::Greater);
+        assert_eq!(compare_build_ids("99", "100"), Ordering::Less);
+        assert_eq!(compare_build_ids("0.16.10", "0.16.9"), Ordering::Greater);
+        assert_eq!(compare_build_ids("1.007", "1.7"), Ordering::Equal);
+        assert_eq!(compare_build_ids("1.2", "1.2.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn sorts_suffixed_builds_before_the_plain_build() {
+        assert_eq!(compare_build_ids("20.4.80-beta", "20.4.80"), Ordering::Less);
+        assert_eq!(
+            compare_build_ids("20.4.80-beta", "20.4.79"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_build_ids("0.6.0-beta.10", "0.6.0-beta.9"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_build_ids("0.6.0-alpha.1", "0.6.0-beta.1"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn orders_forge_builds_by_their_build_number() {
+        assert_eq!(
+            compare_build_ids("10.13.4.1614-1.7.10", "10.13.4.1558-1.7.10"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_build_ids("10.13.4.1614-1.7.10", "10.13.4.1614-1.7.10"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_build_ids("14.23.5.2860", "14.23.5.2859"),
+            Ordering::Greater
+        );
+    }
+}
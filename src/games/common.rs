@@ -1,27 +1,279 @@
+use crate::error::WarehouseError;
+use crate::metrics::metrics;
 use anyhow::Result;
-use reqwest::Client;
+use bytes::Bytes;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode};
 use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct HttpClient {
     client: Client,
+    /// The loader the requests are made for, as reported in metrics.
+    loader: String,
+    upstream: Arc<RwLock<Upstream>>,
+    validated: Arc<Mutex<HashMap<String, ValidatedResponse>>>,
 }
 
-impl Default for HttpClient {
+/// How a loader reaches its upstream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    /// How long a metadata request may take before it fails as an upstream timeout.
+    pub timeout: Duration,
+    /// Upstream base URLs mapped to the mirrors that replace them.
+    pub mirrors: BTreeMap<String, String>,
+}
+
+impl Default for Upstream {
     fn default() -> Self {
         Self {
-            client: Client::new(),
+            timeout: REQUEST_TIMEOUT,
+            mirrors: BTreeMap::new(),
         }
     }
 }
 
+impl Upstream {
+    /// Points `url` at the mirror of the longest base URL it starts with, if any.
+    pub fn resolve(&self, url: &str) -> String {
+        self.mirrors
+            .iter()
+            .filter_map(|(base, mirror)| Some((base, mirror, url.strip_prefix(base.as_str())?)))
+            .max_by_key(|(base, _, _)| base.len())
+            .map_or_else(
+                || url.to_string(),
+                |(_, mirror, rest)| format!("{}{}", mirror, rest),
+            )
+    }
+}
+
+/// A previous response body together with the validators needed to revalidate it.
+#[derive(Debug, Clone)]
+struct ValidatedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: Bytes,
+    used: Instant,
+}
+
+/// How many responses a client keeps for revalidation. Loaders request a bounded set of
+/// URLs per game version, so this covers the ones in active use; the least recently used
+/// response is dropped beyond it and simply fetched in full next time.
+const MAX_VALIDATED: usize = 256;
+
+/// How long a metadata request may take unless the loader is configured otherwise.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 impl HttpClient {
+    pub fn new(loader: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            loader: loader.into(),
+            upstream: Arc::new(RwLock::new(Upstream::default())),
+            validated: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Changes how requests reach upstream, including requests made through clones of this
+    /// client.
+    pub fn configure(&self, upstream: Upstream) {
+        *self.upstream.write().unwrap() = upstream;
+    }
+
+    pub fn upstream(&self) -> Upstream {
+        self.upstream.read().unwrap().clone()
+    }
+
     pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
-        let res = self.client.get(url).send().await?;
-        Ok(res.json::<T>().await?)
+        serde_json::from_slice(&self.get_revalidated(url).await?)
+            .map_err(|e| invalid_response(url, e))
+    }
+
+    pub async fn get_xml<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let body = self.get_revalidated(url).await?;
+        std::str::from_utf8(&body)
+            .map_err(|e| invalid_response(url, e))
+            .and_then(|body| quick_xml::de::from_str(body).map_err(|e| invalid_response(url, e)))
     }
 
-    pub fn get(&self, url: impl AsRef<str>) -> reqwest::RequestBuilder {
-        self.client.get(url.as_ref())
+    /// Fetches `url`, sending `If-None-Match`/`If-Modified-Since` when an earlier response
+    /// carried validators, and reusing that earlier body on `304 Not Modified`.
+    async fn get_revalidated(&self, url: &str) -> Result<Bytes> {
+        let previous = self.validated.lock().unwrap().get(url).cloned();
+
+        let upstream = self.upstream();
+        let mut request = self
+            .client
+            .get(upstream.resolve(url))
+            .timeout(upstream.timeout);
+        if let Some(previous) = &previous {
+            if let Some(etag) = &previous.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &previous.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let started = Instant::now();
+        let res = request.send().await;
+        metrics().observe_upstream(
+            &self.loader,
+            res.as_ref().ok().map(|res| res.status().as_u16()),
+            started.elapsed(),
+        );
+        let res = res?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            if let Some(previous) = previous {
+                if let Some(entry) = self.validated.lock().unwrap().get_mut(url) {
+                    entry.used = Instant::now();
+                }
+                return Ok(previous.body);
+            }
+        }
+
+        let res = res.error_for_status()?;
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = res.bytes().await?;
+
+        if etag.is_some() || last_modified.is_some() {
+            self.remember(
+                url,
+                ValidatedResponse {
+                    etag,
+                    last_modified,
+                    body: body.clone(),
+                    used: Instant::now(),
+                },
+            );
+        }
+
+        Ok(body)
+    }
+
+    /// Keeps `response` for revalidating `url`, dropping the least recently used response
+    /// if the client already keeps [`MAX_VALIDATED`] of them.
+    fn remember(&self, url: &str, response: ValidatedResponse) {
+        let mut validated = self.validated.lock().unwrap();
+        if validated.len() >= MAX_VALIDATED && !validated.contains_key(url) {
+            let oldest = validated
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                validated.remove(&oldest);
+            }
+        }
+        validated.insert(url.to_string(), response);
+    }
+}
+
+/// Upstream answered, but not with the document we expected.
+fn invalid_response(url: &str, error: impl std::fmt::Display) -> anyhow::Error {
+    WarehouseError::Upstream(format!("invalid response from {}: {}", url, error)).into()
+}
+
+/// The `maven-metadata.xml` document published next to every Maven artifact.
+#[derive(Debug, Deserialize)]
+pub struct MavenMetadata {
+    pub versioning: MavenVersioning,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MavenVersioning {
+    pub release: Option<String>,
+    pub versions: MavenVersions,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MavenVersions {
+    #[serde(default)]
+    pub version: Vec<String>,
+}
+
+impl MavenMetadata {
+    /// Versions as listed by the repository, oldest first.
+    pub fn versions(&self) -> &[String] {
+        &self.versioning.versions.version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(n: usize) -> String {
+        format!("https://meta.example.com/{}", n)
+    }
+
+    fn response(used: Instant) -> ValidatedResponse {
+        ValidatedResponse {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            body: Bytes::from_static(b"[]"),
+            used,
+        }
+    }
+
+    #[test]
+    fn drops_the_least_recently_used_response_beyond_the_limit() {
+        let client = HttpClient::new("test");
+        let start = Instant::now();
+        let at = |secs: usize| start + Duration::from_secs(secs as u64);
+        for n in 0..MAX_VALIDATED {
+            client.remember(&url(n), response(at(n)));
+        }
+
+        // Replacing a kept response makes no room, and makes it the most recently used.
+        client.remember(&url(0), response(at(MAX_VALIDATED)));
+        assert_eq!(client.validated.lock().unwrap().len(), MAX_VALIDATED);
+
+        client.remember(&url(MAX_VALIDATED), response(at(MAX_VALIDATED + 1)));
+        let validated = client.validated.lock().unwrap();
+        assert_eq!(validated.len(), MAX_VALIDATED);
+        assert!(validated.contains_key(&url(0)));
+        assert!(!validated.contains_key(&url(1)));
+        assert!(validated.contains_key(&url(MAX_VALIDATED)));
+    }
+
+    #[test]
+    fn resolves_urls_to_the_mirror_of_the_longest_matching_base() {
+        let upstream = Upstream {
+            mirrors: BTreeMap::from([
+                (
+                    "https://api.papermc.io".to_string(),
+                    "https://mirror.example.com".to_string(),
+                ),
+                (
+                    "https://api.papermc.io/v2/projects/paper".to_string(),
+                    "https://paper.example.com".to_string(),
+                ),
+            ]),
+            ..Upstream::default()
+        };
+
+        assert_eq!(
+            upstream.resolve("https://api.papermc.io/v2/projects/paper/versions"),
+            "https://paper.example.com/versions"
+        );
+        assert_eq!(
+            upstream.resolve("https://api.papermc.io/v2/projects/velocity"),
+            "https://mirror.example.com/v2/projects/velocity"
+        );
+        assert_eq!(
+            upstream.resolve("https://meta.fabricmc.net/v2/versions"),
+            "https://meta.fabricmc.net/v2/versions"
+        );
     }
 }
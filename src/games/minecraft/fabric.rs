@@ -0,0 +1,138 @@
+use crate::error::WarehouseError;
+use crate::game::{Build, BuildOptions, GameLoader, Version, VersionType};
+use crate::games::common::HttpClient;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const FABRIC_META: &str = "https://meta.fabricmc.net/v2";
+
+#[derive(Debug, Clone)]
+pub struct FabricLoader {
+    client: HttpClient,
+}
+
+impl Default for FabricLoader {
+    fn default() -> Self {
+        Self {
+            client: HttpClient::new("fabric"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GameVersion {
+    version: String,
+    stable: bool,
+}
+
+#[derive(Deserialize)]
+struct LoaderEntry {
+    loader: LoaderVersion,
+}
+
+#[derive(Deserialize)]
+struct LoaderVersion {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct InstallerVersion {
+    version: String,
+    stable: bool,
+}
+
+impl FabricLoader {
+    /// The requested installer if Fabric publishes it, or else the latest stable one.
+    async fn installer(&self, requested: Option<&str>) -> anyhow::Result<String> {
+        let installers: Vec<InstallerVersion> = self
+            .client
+            .get_json(&format!("{}/versions/installer", FABRIC_META))
+            .await?;
+
+        let installer = match requested {
+            Some(requested) => installers.into_iter().find(|i| i.version == requested),
+            None => installers.into_iter().find(|i| i.stable),
+        };
+        installer.map(|i| i.version).ok_or_else(|| {
+            WarehouseError::BuildNotFound(match requested {
+                Some(requested) => format!("Fabric installer '{}' not found", requested),
+                None => "No stable Fabric installer available".to_string(),
+            })
+            .into()
+        })
+    }
+
+    fn build(version: &Version, loader: String, installer: &str) -> Build {
+        let url = format!(
+            "{}/versions/loader/{}/{}/{}/server/jar",
+            FABRIC_META,
+            version.id(),
+            loader,
+            installer
+        );
+        let filename = format!(
+            "fabric-server-mc.{}-loader.{}-launcher.{}.jar",
+            version.id(),
+            loader,
+            installer
+        );
+        Build::new(loader, version.clone(), Some(url)).with_filename(filename)
+    }
+}
+
+#[async_trait]
+impl GameLoader for FabricLoader {
+    fn name(&self) -> &str {
+        "fabric"
+    }
+
+    fn website(&self) -> Option<&str> {
+        Some("https://fabricmc.net")
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let versions: Vec<GameVersion> = self
+            .client
+            .get_json(&format!("{}/versions/game", FABRIC_META))
+            .await?;
+
+        Ok(versions
+            .into_iter()
+            .map(|v| {
+                let version_type = if v.stable {
+                    VersionType::Release
+                } else {
+                    VersionType::from_snapshot_id(&v.version)
+                };
+                Version::new(v.version, version_type, v.stable)
+            })
+            .collect())
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        self.fetch_builds_with(version, &BuildOptions::default())
+            .await
+    }
+
+    async fn fetch_builds_with(
+        &self,
+        version: &Version,
+        options: &BuildOptions,
+    ) -> anyhow::Result<Vec<Build>> {
+        let installer = self.installer(options.installer.as_deref()).await?;
+
+        let loaders: Vec<LoaderEntry> = self
+            .client
+            .get_json(&format!("{}/versions/loader/{}", FABRIC_META, version.id()))
+            .await?;
+
+        Ok(loaders
+            .into_iter()
+            .map(|entry| Self::build(version, entry.loader.version, &installer))
+            .collect())
+    }
+}
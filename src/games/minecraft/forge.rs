@@ -0,0 +1,133 @@
+use crate::game::{ArtifactKind, Build, GameLoader, Version, VersionType};
+use crate::games::common::{HttpClient, MavenMetadata};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+const FORGE_MAVEN: &str = "https://maven.minecraftforge.net/net/minecraftforge/forge";
+const FORGE_PROMOTIONS: &str =
+    "https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json";
+
+#[derive(Debug, Clone)]
+pub struct ForgeLoader {
+    client: HttpClient,
+}
+
+impl Default for ForgeLoader {
+    fn default() -> Self {
+        Self {
+            client: HttpClient::new("forge"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Promotions {
+    promos: HashMap<String, String>,
+}
+
+impl Promotions {
+    fn recommended(&self, minecraft: &str) -> Option<&str> {
+        self.promos
+            .get(&format!("{}-recommended", minecraft))
+            .map(String::as_str)
+    }
+}
+
+impl ForgeLoader {
+    async fn fetch_promotions(&self) -> anyhow::Result<Promotions> {
+        self.client.get_json(FORGE_PROMOTIONS).await
+    }
+
+    async fn fetch_metadata(&self) -> anyhow::Result<MavenMetadata> {
+        self.client
+            .get_xml(&format!("{}/maven-metadata.xml", FORGE_MAVEN))
+            .await
+    }
+
+    /// Maven versions are `{minecraft}-{forge}`, e.g. `1.20.1-47.2.0`.
+    fn split_version(maven: &str) -> Option<(&str, &str)> {
+        maven.split_once('-')
+    }
+
+    /// Orders Forge versions numerically so that `47.10.0` sorts after `47.9.0`.
+    fn compare_forge(a: &str, b: &str) -> Ordering {
+        let parts = |s: &str| {
+            s.split(['.', '-'])
+                .map(|p| p.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>()
+        };
+        parts(a).cmp(&parts(b))
+    }
+}
+
+#[async_trait]
+impl GameLoader for ForgeLoader {
+    fn name(&self) -> &str {
+        "forge"
+    }
+
+    fn website(&self) -> Option<&str> {
+        Some("https://minecraftforge.net")
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let (metadata, promotions) =
+            tokio::try_join!(self.fetch_metadata(), self.fetch_promotions())?;
+
+        let mut ids: Vec<&str> = Vec::new();
+        for maven in metadata.versions() {
+            if let Some((minecraft, _)) = Self::split_version(maven) {
+                if !ids.contains(&minecraft) {
+                    ids.push(minecraft);
+                }
+            }
+        }
+        ids.sort_by(|a, b| Self::compare_forge(b, a));
+
+        Ok(ids
+            .into_iter()
+            .map(|id| {
+                let is_stable = promotions.recommended(id).is_some();
+                Version::new(id.to_string(), VersionType::from_id(id), is_stable)
+            })
+            .collect())
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        let (metadata, promotions) =
+            tokio::try_join!(self.fetch_metadata(), self.fetch_promotions())?;
+        let recommended = promotions.recommended(version.id());
+
+        let mut builds = metadata
+            .versions()
+            .iter()
+            .filter_map(|maven| {
+                let (minecraft, forge) = Self::split_version(maven)?;
+                (minecraft == version.id()).then_some((maven.as_str(), forge))
+            })
+            .collect::<Vec<_>>();
+        builds.sort_by(|(_, a), (_, b)| Self::compare_forge(b, a));
+
+        Ok(builds
+            .into_iter()
+            .map(|(maven, forge)| {
+                let filename = format!("forge-{}-installer.jar", maven);
+                let url = format!("{}/{}/{}", FORGE_MAVEN, maven, filename);
+                // Only the promoted "recommended" build is considered stable; Forge
+                // publishes every other build as-is without any stability guarantee.
+                let is_stable = recommended
+                    .is_some_and(|r| forge == r || forge.starts_with(&format!("{}-", r)));
+                Build::new(forge.to_string(), version.clone(), Some(url))
+                    .with_artifact(ArtifactKind::Installer)
+                    .with_filename(filename)
+                    .with_stable(is_stable)
+            })
+            .collect())
+    }
+}
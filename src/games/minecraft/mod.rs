@@ -1,11 +1,30 @@
+pub mod fabric;
+pub mod forge;
+pub mod neoforge;
+pub mod paper;
+pub mod purpur;
+pub mod quilt;
 pub mod vanilla;
 
 use crate::game::Game;
 
+use fabric::FabricLoader;
+use forge::ForgeLoader;
+use neoforge::NeoForgeLoader;
+use paper::PaperLoader;
+use purpur::PurpurLoader;
+use quilt::QuiltLoader;
 use vanilla::VanillaLoader;
 
 pub fn minecraft() -> Game {
     let mut minecraft = Game::new("minecraft".to_string());
     minecraft.add_loader(VanillaLoader::default());
+    minecraft.add_loader(PaperLoader::paper());
+    minecraft.add_loader(PaperLoader::folia());
+    minecraft.add_loader(PurpurLoader::default());
+    minecraft.add_loader(FabricLoader::default());
+    minecraft.add_loader(QuiltLoader::default());
+    minecraft.add_loader(ForgeLoader::default());
+    minecraft.add_loader(NeoForgeLoader::default());
     minecraft
 }
@@ -0,0 +1,98 @@
+use crate::game::{ArtifactKind, Build, GameLoader, Version, VersionType};
+use crate::games::common::{HttpClient, MavenMetadata};
+use async_trait::async_trait;
+
+const NEOFORGE_MAVEN: &str = "https://maven.neoforged.net/releases/net/neoforged/neoforge";
+
+#[derive(Debug, Clone)]
+pub struct NeoForgeLoader {
+    client: HttpClient,
+}
+
+impl Default for NeoForgeLoader {
+    fn default() -> Self {
+        Self {
+            client: HttpClient::new("neoforge"),
+        }
+    }
+}
+
+impl NeoForgeLoader {
+    async fn fetch_metadata(&self) -> anyhow::Result<MavenMetadata> {
+        self.client
+            .get_xml(&format!("{}/maven-metadata.xml", NEOFORGE_MAVEN))
+            .await
+    }
+
+    /// NeoForge versions encode the Minecraft version they target: `20.4.80-beta`
+    /// is built for 1.20.4 and `21.0.10` for 1.21.
+    fn minecraft_version(neoforge: &str) -> Option<String> {
+        let mut parts = neoforge.split('.');
+        let major = parts.next()?.parse::<u32>().ok()?;
+        let minor = parts.next()?.parse::<u32>().ok()?;
+        Some(if minor == 0 {
+            format!("1.{}", major)
+        } else {
+            format!("1.{}.{}", major, minor)
+        })
+    }
+
+    fn is_stable(neoforge: &str) -> bool {
+        !neoforge.contains("beta") && !neoforge.contains("alpha")
+    }
+}
+
+#[async_trait]
+impl GameLoader for NeoForgeLoader {
+    fn name(&self) -> &str {
+        "neoforge"
+    }
+
+    fn website(&self) -> Option<&str> {
+        Some("https://neoforged.net")
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let metadata = self.fetch_metadata().await?;
+
+        let mut versions: Vec<Version> = Vec::new();
+        for build in metadata.versions().iter().rev() {
+            let Some(id) = Self::minecraft_version(build) else {
+                continue;
+            };
+            match versions.iter_mut().find(|v| v.id() == id) {
+                Some(version) => version.is_stable |= Self::is_stable(build),
+                None => versions.push(Version::new(
+                    id,
+                    VersionType::Release,
+                    Self::is_stable(build),
+                )),
+            }
+        }
+
+        Ok(versions)
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        let metadata = self.fetch_metadata().await?;
+
+        Ok(metadata
+            .versions()
+            .iter()
+            .rev()
+            .filter(|build| Self::minecraft_version(build).as_deref() == Some(version.id()))
+            .map(|build| {
+                let filename = format!("neoforge-{}-installer.jar", build);
+                let url = format!("{}/{}/{}", NEOFORGE_MAVEN, build, filename);
+                Build::new(build.clone(), version.clone(), Some(url))
+                    .with_artifact(ArtifactKind::Installer)
+                    .with_filename(filename)
+                    .with_stable(Self::is_stable(build))
+            })
+            .collect())
+    }
+}
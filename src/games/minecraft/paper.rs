@@ -0,0 +1,161 @@
+use crate::game::{Build, Change, GameLoader, Version, VersionType};
+use crate::games::common::HttpClient;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+const PAPER_API: &str = "https://api.papermc.io/v2";
+
+#[derive(Debug, Clone)]
+pub struct PaperLoader {
+    client: HttpClient,
+    project: String,
+    website: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectResponse {
+    versions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct BuildsResponse {
+    builds: Vec<BuildEntry>,
+}
+
+#[derive(Deserialize)]
+struct BuildEntry {
+    build: u32,
+    time: Option<DateTime<Utc>>,
+    channel: String,
+    #[serde(default)]
+    changes: Vec<ChangeEntry>,
+    downloads: BuildDownloads,
+}
+
+#[derive(Deserialize)]
+struct ChangeEntry {
+    commit: String,
+    summary: String,
+}
+
+#[derive(Deserialize)]
+struct BuildDownloads {
+    application: DownloadInfo,
+}
+
+#[derive(Deserialize)]
+struct DownloadInfo {
+    name: String,
+    sha256: String,
+}
+
+impl PaperLoader {
+    pub fn new(project: impl Into<String>, website: impl Into<String>) -> Self {
+        let project = project.into();
+        Self {
+            client: HttpClient::new(project.clone()),
+            project,
+            website: website.into(),
+        }
+    }
+
+    pub fn paper() -> Self {
+        Self::new("paper", "https://papermc.io/software/paper")
+    }
+
+    pub fn folia() -> Self {
+        Self::new("folia", "https://papermc.io/software/folia")
+    }
+
+    pub fn velocity() -> Self {
+        Self::new("velocity", "https://papermc.io/software/velocity")
+    }
+
+    pub fn waterfall() -> Self {
+        Self::new("waterfall", "https://papermc.io/software/waterfall")
+    }
+
+    fn download_url(&self, version: &str, build: u32, name: &str) -> String {
+        format!(
+            "{}/projects/{}/versions/{}/builds/{}/downloads/{}",
+            PAPER_API, self.project, version, build, name
+        )
+    }
+}
+
+#[async_trait]
+impl GameLoader for PaperLoader {
+    fn name(&self) -> &str {
+        &self.project
+    }
+
+    fn website(&self) -> Option<&str> {
+        Some(&self.website)
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let project: ProjectResponse = self
+            .client
+            .get_json(&format!("{}/projects/{}", PAPER_API, self.project))
+            .await?;
+
+        // The API lists versions oldest first; keep newest first like the vanilla manifest.
+        Ok(project
+            .versions
+            .into_iter()
+            .rev()
+            .map(|id| {
+                let version_type = VersionType::from_id(&id);
+                Version::new_standard(id, version_type)
+            })
+            .collect())
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        let response: BuildsResponse = self
+            .client
+            .get_json(&format!(
+                "{}/projects/{}/versions/{}/builds",
+                PAPER_API,
+                self.project,
+                version.id()
+            ))
+            .await?;
+
+        Ok(response
+            .builds
+            .into_iter()
+            .rev()
+            .map(|entry| {
+                let download = entry.downloads.application;
+                let build = Build::new(
+                    entry.build.to_string(),
+                    version.clone(),
+                    Some(self.download_url(version.id(), entry.build, &download.name)),
+                )
+                .with_filename(download.name)
+                .with_sha256(download.sha256)
+                .with_stable(entry.channel == "default")
+                .with_changes(
+                    entry
+                        .changes
+                        .into_iter()
+                        .map(|change| Change {
+                            commit: Some(change.commit),
+                            summary: change.summary,
+                        })
+                        .collect(),
+                );
+                match entry.time {
+                    Some(time) => build.with_release_time(time),
+                    None => build,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,136 @@
+use crate::game::{Build, Change, GameLoader, Version, VersionType};
+use crate::games::common::HttpClient;
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde::Deserialize;
+
+const PURPUR_API: &str = "https://api.purpurmc.org/v2/purpur";
+
+#[derive(Debug, Clone)]
+pub struct PurpurLoader {
+    client: HttpClient,
+}
+
+impl Default for PurpurLoader {
+    fn default() -> Self {
+        Self {
+            client: HttpClient::new("purpur"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProjectResponse {
+    versions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct VersionResponse {
+    builds: VersionBuilds,
+}
+
+#[derive(Deserialize)]
+struct VersionBuilds {
+    all: Vec<BuildEntry>,
+}
+
+#[derive(Deserialize)]
+struct BuildEntry {
+    build: String,
+    result: BuildResult,
+    md5: Option<String>,
+    timestamp: i64,
+    #[serde(default)]
+    commits: Vec<CommitEntry>,
+}
+
+#[derive(Deserialize)]
+struct CommitEntry {
+    hash: String,
+    description: String,
+}
+
+#[derive(Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+enum BuildResult {
+    Success,
+    Failure,
+    #[serde(other)]
+    Unknown,
+}
+
+impl PurpurLoader {
+    fn download_url(version: &str, build: &str) -> String {
+        format!("{}/{}/{}/download", PURPUR_API, version, build)
+    }
+}
+
+#[async_trait]
+impl GameLoader for PurpurLoader {
+    fn name(&self) -> &str {
+        "purpur"
+    }
+
+    fn website(&self) -> Option<&str> {
+        Some("https://purpurmc.org")
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let project: ProjectResponse = self.client.get_json(PURPUR_API).await?;
+
+        Ok(project
+            .versions
+            .into_iter()
+            .rev()
+            .map(|id| {
+                let version_type = VersionType::from_id(&id);
+                Version::new_standard(id, version_type)
+            })
+            .collect())
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        let response: VersionResponse = self
+            .client
+            .get_json(&format!("{}/{}?detailed=true", PURPUR_API, version.id()))
+            .await?;
+
+        let mut builds = response
+            .builds
+            .all
+            .into_iter()
+            .filter(|entry| entry.result == BuildResult::Success)
+            .collect::<Vec<_>>();
+        builds.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+
+        Ok(builds
+            .into_iter()
+            .map(|entry| {
+                let url = Self::download_url(version.id(), &entry.build);
+                let filename = format!("purpur-{}-{}.jar", version.id(), entry.build);
+                let changes = entry
+                    .commits
+                    .into_iter()
+                    .map(|commit| Change {
+                        commit: Some(commit.hash),
+                        summary: commit.description,
+                    })
+                    .collect();
+                let mut build = Build::new(entry.build, version.clone(), Some(url))
+                    .with_filename(filename)
+                    .with_changes(changes);
+                if let Some(time) = DateTime::from_timestamp_millis(entry.timestamp) {
+                    build = build.with_release_time(time);
+                }
+                match entry.md5 {
+                    Some(md5) => build.with_md5(md5),
+                    None => build,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::WarehouseError;
+use crate::game::{ArtifactKind, Build, BuildOptions, GameLoader, Version, VersionType};
+use crate::games::common::{HttpClient, MavenMetadata};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const QUILT_META: &str = "https://meta.quiltmc.org/v3";
+const QUILT_MAVEN: &str = "https://maven.quiltmc.org/repository/release/org/quiltmc";
+
+#[derive(Debug, Clone)]
+pub struct QuiltLoader {
+    client: HttpClient,
+}
+
+impl Default for QuiltLoader {
+    fn default() -> Self {
+        Self {
+            client: HttpClient::new("quilt"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GameVersion {
+    version: String,
+    stable: bool,
+}
+
+#[derive(Deserialize)]
+struct LoaderEntry {
+    loader: LoaderVersion,
+}
+
+#[derive(Deserialize)]
+struct LoaderVersion {
+    version: String,
+}
+
+impl QuiltLoader {
+    /// The requested installer if Quilt publishes it, or else the latest one.
+    async fn installer(&self, requested: Option<&str>) -> anyhow::Result<String> {
+        let metadata: MavenMetadata = self
+            .client
+            .get_xml(&format!(
+                "{}/quilt-installer/maven-metadata.xml",
+                QUILT_MAVEN
+            ))
+            .await?;
+
+        let installer = match requested {
+            Some(requested) => metadata
+                .versions()
+                .iter()
+                .find(|v| *v == requested)
+                .cloned(),
+            None => metadata
+                .versioning
+                .release
+                .clone()
+                .or_else(|| metadata.versions().last().cloned()),
+        };
+        installer.ok_or_else(|| {
+            WarehouseError::BuildNotFound(match requested {
+                Some(requested) => format!("Quilt installer '{}' not found", requested),
+                None => "No Quilt installer available".to_string(),
+            })
+            .into()
+        })
+    }
+}
+
+#[async_trait]
+impl GameLoader for QuiltLoader {
+    fn name(&self) -> &str {
+        "quilt"
+    }
+
+    fn website(&self) -> Option<&str> {
+        Some("https://quiltmc.org")
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let versions: Vec<GameVersion> = self
+            .client
+            .get_json(&format!("{}/versions/game", QUILT_META))
+            .await?;
+
+        Ok(versions
+            .into_iter()
+            .map(|v| {
+                let version_type = if v.stable {
+                    VersionType::Release
+                } else {
+                    VersionType::from_snapshot_id(&v.version)
+                };
+                Version::new(v.version, version_type, v.stable)
+            })
+            .collect())
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        self.fetch_builds_with(version, &BuildOptions::default())
+            .await
+    }
+
+    async fn fetch_builds_with(
+        &self,
+        version: &Version,
+        options: &BuildOptions,
+    ) -> anyhow::Result<Vec<Build>> {
+        let installer = self.installer(options.installer.as_deref()).await?;
+
+        // Only loader builds that support the game version are listed, newest first.
+        let loaders: Vec<LoaderEntry> = self
+            .client
+            .get_json(&format!("{}/versions/loader/{}", QUILT_META, version.id()))
+            .await?;
+
+        // Quilt publishes no server jars, and its installer is not tied to a loader build:
+        // the same jar installs any of them, so every build shares one artifact and the
+        // build ID has to be passed to the installer as the loader version.
+        let filename = format!("quilt-installer-{}.jar", installer);
+        let url = format!("{}/quilt-installer/{}/{}", QUILT_MAVEN, installer, filename);
+
+        Ok(loaders
+            .into_iter()
+            .map(|entry| {
+                let loader = entry.loader.version;
+                let stable = !loader.contains("beta");
+                Build::new(loader, version.clone(), Some(url.clone()))
+                    .with_artifact(ArtifactKind::Installer)
+                    .with_filename(filename.clone())
+                    .with_stable(stable)
+            })
+            .collect())
+    }
+}
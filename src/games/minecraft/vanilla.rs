@@ -1,36 +1,23 @@
-use crate::game::{Build, GameLoader, Version};
+use crate::error::WarehouseError;
+use crate::game::{Build, GameLoader, Version, VersionType};
 use crate::games::common::HttpClient;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
-pub enum VersionType {
-    #[serde(rename = "release")]
-    Release,
-    #[serde(rename = "snapshot")]
-    Snapshot,
-    #[serde(rename = "old_beta")]
-    OldBeta,
-    #[serde(rename = "old_alpha")]
-    OldAlpha,
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+#[derive(Debug, Clone)]
+pub struct VanillaLoader {
+    client: HttpClient,
 }
 
-impl std::fmt::Display for VersionType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Release => write!(f, "release"),
-            Self::Snapshot => write!(f, "snapshot"),
-            Self::OldBeta => write!(f, "old_beta"),
-            Self::OldAlpha => write!(f, "old_alpha"),
+impl Default for VanillaLoader {
+    fn default() -> Self {
+        Self {
+            client: HttpClient::new("vanilla"),
         }
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct VanillaLoader {
-    client: HttpClient,
-}
-
 #[derive(Deserialize)]
 struct VersionManifest {
     versions: Vec<VersionManifestEntry>,
@@ -42,31 +29,44 @@ struct VersionManifestEntry {
     #[serde(rename = "type")]
     version_type: String,
     url: String,
+    #[serde(rename = "releaseTime")]
+    release_time: DateTime<Utc>,
 }
 
 #[derive(Deserialize)]
 struct VersionMetadata {
     downloads: VersionDownloads,
+    /// Missing from versions older than 1.6.
+    #[serde(rename = "javaVersion")]
+    java_version: Option<JavaVersion>,
+}
+
+#[derive(Deserialize)]
+struct JavaVersion {
+    #[serde(rename = "majorVersion")]
+    major_version: u32,
 }
 
 #[derive(Deserialize)]
 struct VersionDownloads {
-    server: DownloadInfo,
+    /// Missing from versions that were only released as a client, such as the Classic era.
+    server: Option<DownloadInfo>,
 }
 
 #[derive(Deserialize)]
 struct DownloadInfo {
     url: String,
+    sha1: String,
+    size: u64,
 }
 
 impl VanillaLoader {
-    fn parse_version_type(type_str: &str) -> VersionType {
+    /// The manifest files pre-releases and release candidates under "snapshot", so
+    /// those are told apart by id. Types the manifest may add later are kept as-is.
+    fn parse_version_type(type_str: &str, id: &str) -> VersionType {
         match type_str {
-            "release" => VersionType::Release,
-            "snapshot" => VersionType::Snapshot,
-            "old_beta" => VersionType::OldBeta,
-            "old_alpha" => VersionType::OldAlpha,
-            _ => VersionType::Snapshot,
+            "snapshot" => VersionType::from_snapshot_id(id),
+            other => VersionType::from(other),
         }
     }
 }
@@ -81,10 +81,14 @@ impl GameLoader for VanillaLoader {
         Some("https://www.minecraft.net")
     }
 
-    fn supports_version_type(&self, _version_type: &str) -> bool {
+    fn supports_version_type(&self, _version_type: &VersionType) -> bool {
         true
     }
 
+    fn http_client(&self) -> Option<&HttpClient> {
+        Some(&self.client)
+    }
+
     async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
         let manifest: VersionManifest = self
             .client
@@ -95,12 +99,8 @@ impl GameLoader for VanillaLoader {
             .versions
             .into_iter()
             .map(|entry| {
-                let version_type = Self::parse_version_type(&entry.version_type);
-                Version::new(
-                    entry.id,
-                    version_type.to_string(),
-                    version_type == VersionType::Release,
-                )
+                let version_type = Self::parse_version_type(&entry.version_type, &entry.id);
+                Version::new_standard(entry.id, version_type).with_release_time(entry.release_time)
             })
             .collect())
     }
@@ -108,30 +108,54 @@ impl GameLoader for VanillaLoader {
     async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
         let manifest: VersionManifest = self
             .client
-            .get("https://launchermeta.mojang.com/mc/game/version_manifest_v2.json")
-            .send()
-            .await?
-            .json()
+            .get_json("https://launchermeta.mojang.com/mc/game/version_manifest_v2.json")
             .await?;
 
         let version_entry = manifest
             .versions
             .into_iter()
             .find(|v| v.id == version.id())
-            .ok_or_else(|| anyhow::anyhow!("Version not found"))?;
-
-        let metadata: VersionMetadata = self
-            .client
-            .get(version_entry.url)
-            .send()
-            .await?
-            .json()
-            .await?;
+            .ok_or_else(|| WarehouseError::VersionNotFound(version.id().to_string()))?;
+
+        let metadata: VersionMetadata = self.client.get_json(&version_entry.url).await?;
+
+        // Without a server jar the version has no builds to offer.
+        let Some(server) = metadata.downloads.server else {
+            return Ok(Vec::new());
+        };
+        let build = Build::new(version_entry.id, version.clone(), Some(server.url))
+            .with_sha1(server.sha1)
+            .with_size(server.size)
+            .with_release_time(version_entry.release_time);
+        Ok(vec![match metadata.java_version {
+            Some(java) => build.with_java_version(java.major_version),
+            None => build,
+        }])
+    }
+}
 
-        Ok(vec![Build::new(
-            version_entry.id,
-            version.clone(),
-            Some(metadata.downloads.server.url),
-        )])
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions_without_a_server_jar() {
+        let metadata: VersionMetadata = serde_json::from_str(
+            r#"{"downloads": {"client": {"url": "https://example.com/client.jar", "sha1": "ab", "size": 1}}}"#,
+        )
+        .unwrap();
+        assert!(metadata.downloads.server.is_none());
+        assert!(metadata.java_version.is_none());
+
+        let metadata: VersionMetadata = serde_json::from_str(
+            r#"{
+                "downloads": {"server": {"url": "https://example.com/server.jar", "sha1": "cd", "size": 2}},
+                "javaVersion": {"majorVersion": 21}
+            }"#,
+        )
+        .unwrap();
+        let server = metadata.downloads.server.unwrap();
+        assert_eq!((server.sha1.as_str(), server.size), ("cd", 2));
+        assert_eq!(metadata.java_version.unwrap().major_version, 21);
     }
 }
@@ -0,0 +1,9 @@
+use crate::game::Game;
+use crate::games::minecraft::paper::PaperLoader;
+
+pub fn proxy() -> Game {
+    let mut proxy = Game::new("proxy".to_string());
+    proxy.add_loader(PaperLoader::velocity());
+    proxy.add_loader(PaperLoader::waterfall());
+    proxy
+}
@@ -0,0 +1,133 @@
+use crate::AppState;
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadyStatus {
+    /// Storage is writable and every upstream answered its last refresh.
+    Ok,
+    /// Some upstream is failing; its cached data, if any, is still served.
+    Degraded,
+    /// Storage is not writable, so nothing new can be downloaded.
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LoaderStatus {
+    Ok,
+    Failing,
+    /// No versions or builds have been fetched yet.
+    Unknown,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StorageReadiness {
+    pub writable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoaderReadiness {
+    pub game: String,
+    pub loader: String,
+    pub status: LoaderStatus,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Whether versions can be listed from the metadata cache.
+    pub cached: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    pub status: ReadyStatus,
+    pub storage: StorageReadiness,
+    pub loaders: Vec<LoaderReadiness>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "warehouse",
+    responses(
+        (status = 200, description = "The process is alive", body = String)
+    )
+)]
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    HttpResponse::Ok().body("ok")
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "warehouse",
+    responses(
+        (status = 200, description = "Ready to serve, possibly degraded", body = Readiness),
+        (status = 503, description = "Storage is not writable", body = Readiness)
+    )
+)]
+#[get("/readyz")]
+pub async fn readyz(state: web::Data<AppState>) -> impl Responder {
+    let storage = match state.games.cache.check_writable().await {
+        Ok(()) => StorageReadiness {
+            writable: true,
+            error: None,
+        },
+        Err(e) => StorageReadiness {
+            writable: false,
+            error: Some(format!("{:#}", e)),
+        },
+    };
+
+    let mut loaders = Vec::new();
+    for game in state.games.list_games().await {
+        for loader in game.list_loaders() {
+            let Some(health) = loader.health() else {
+                continue;
+            };
+            let status = if health.failing() {
+                LoaderStatus::Failing
+            } else if health.last_success.is_some() {
+                LoaderStatus::Ok
+            } else {
+                LoaderStatus::Unknown
+            };
+            loaders.push(LoaderReadiness {
+                game: game.id().to_string(),
+                loader: loader.name().to_string(),
+                status,
+                last_success: health.last_success,
+                last_failure: health.last_failure,
+                last_error: health.last_error,
+                cached: health.cached,
+            });
+        }
+    }
+
+    loaders.sort_by(|a, b| (&a.game, &a.loader).cmp(&(&b.game, &b.loader)));
+
+    let status = if !storage.writable {
+        ReadyStatus::Unavailable
+    } else if loaders.iter().any(|l| l.status == LoaderStatus::Failing) {
+        ReadyStatus::Degraded
+    } else {
+        ReadyStatus::Ok
+    };
+
+    let readiness = Readiness {
+        status,
+        storage,
+        loaders,
+    };
+    if status == ReadyStatus::Unavailable {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    } else {
+        HttpResponse::Ok().json(readiness)
+    }
+}
@@ -1,18 +1,35 @@
 mod api;
 mod cache;
+mod checksum;
 mod config;
+mod error;
 mod game;
 mod games;
+mod health;
+mod metadata;
+mod metrics;
+mod reload;
+mod singleflight;
+mod storage;
+mod version_order;
 
+use actix_web::dev::Service;
 use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
+use anyhow::Context;
+use api::v1::models::DownloadMode;
 use config::Settings;
 use game::GameProvider;
 use games::minecraft::minecraft;
-use std::{sync::Arc, time::Duration};
+use games::proxy::proxy;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+/// How often cache hits buffered in memory are written to the cache index.
+const INDEX_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(OpenApi)]
 #[openapi(
     paths(
@@ -20,6 +37,8 @@ use utoipa_swagger_ui::SwaggerUi;
         api::v1::routes::list_versions,
         api::v1::routes::list_builds,
         api::v1::routes::download_version,
+        health::healthz,
+        health::readyz,
     ),
     components(
         schemas(
@@ -27,10 +46,19 @@ use utoipa_swagger_ui::SwaggerUi;
             api::v1::models::GameInfo,
             api::v1::models::VersionInfo,
             api::v1::models::BuildInfo,
+            api::v1::models::ChecksumInfo,
+            api::v1::models::ChangeInfo,
+            api::v1::models::DownloadMode,
+            api::v1::models::SortOrder,
             api::v1::models::ErrorResponse,
             api::v1::models::VersionQuery,
             api::v1::models::BuildQuery,
             api::v1::models::DownloadQuery,
+            health::Readiness,
+            health::ReadyStatus,
+            health::StorageReadiness,
+            health::LoaderReadiness,
+            health::LoaderStatus,
         )
     ),
     tags(
@@ -52,14 +80,39 @@ async fn api_root() -> impl Responder {
     HttpResponse::Ok().body(format!("running warehouse {}", env!("CARGO_PKG_VERSION")))
 }
 
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "warehouse",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String)
+    )
+)]
+#[get("/metrics")]
+async fn metrics_endpoint(state: web::Data<AppState>) -> impl Responder {
+    let metrics = metrics::metrics();
+    match state.games.cache.usage().await {
+        Ok((entries, bytes)) => {
+            metrics.cache_entries.set(entries as i64);
+            metrics.cache_size.set(bytes as i64);
+        }
+        Err(e) => error!("failed to measure cache usage: {}", e),
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render())
+}
+
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub games: Arc<GameProvider>,
+    pub download_mode: DownloadMode,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let settings = Settings::new().expect("Failed to load configuration");
+    let settings = Settings::new().context("failed to load configuration")?;
 
     tracing_subscriber::fmt()
         .with_max_level(
@@ -72,28 +125,70 @@ async fn main() -> anyhow::Result<()> {
 
     info!("starting warehouse {}", env!("CARGO_PKG_VERSION"));
 
-    let games = Arc::new(GameProvider::from_settings(&settings));
-    games.register_game(minecraft()).await;
+    let games = Arc::new(GameProvider::from_settings(&settings)?);
+    games.cache.recover().await?;
+    games
+        .register_games(vec![minecraft(), proxy()])
+        .await
+        .context("invalid configuration")?;
 
     let games_clone = games.clone();
     tokio::spawn(async move {
         loop {
+            games_clone.refresh_pinned_builds().await;
             if let Err(e) = games_clone.cache.cleanup().await {
                 error!("cache cleanup failed: {}", e);
             }
-            tokio::time::sleep(Duration::from_secs(settings.cache_ttl)).await;
+            let cache_ttl = games_clone.settings().await.cache_ttl;
+            tokio::time::sleep(Duration::from_secs(cache_ttl)).await;
+        }
+    });
+
+    let games_clone = games.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(INDEX_FLUSH_INTERVAL).await;
+            if let Err(e) = games_clone.cache.flush().await {
+                error!("cache index flush failed: {}", e);
+            }
         }
     });
 
-    let app_state = AppState { games };
+    tokio::spawn(reload::watch(games.clone(), config::config_path()));
+
+    let app_state = AppState {
+        games: games.clone(),
+        download_mode: settings.download_mode,
+    };
 
     let bind_address = settings.bind_address.clone();
     let server = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(app_state.clone()))
             .wrap(actix_web::middleware::Logger::default())
+            .wrap_fn(|req, srv| {
+                let started = Instant::now();
+                let response = srv.call(req);
+                async move {
+                    let response = response.await?;
+                    let route = response
+                        .request()
+                        .match_pattern()
+                        .unwrap_or_else(|| "unmatched".to_string());
+                    metrics::metrics().observe_request(
+                        &route,
+                        response.request().method().as_str(),
+                        response.status().as_u16(),
+                        started.elapsed(),
+                    );
+                    Ok(response)
+                }
+            })
             .service(SwaggerUi::new("/docs/{_:.*}").url("/docs/openapi.json", ApiDoc::openapi()))
             .service(api_root)
+            .service(metrics_endpoint)
+            .service(health::healthz)
+            .service(health::readyz)
             .configure(api::v1::configure)
     })
     .bind(bind_address)?
@@ -109,5 +204,7 @@ async fn main() -> anyhow::Result<()> {
         },
     }
 
+    games.cache.flush().await?;
+
     Ok(())
 }
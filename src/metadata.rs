@@ -0,0 +1,365 @@
+use crate::error::WarehouseError;
+use crate::game::{Build, BuildOptions, GameLoader, Version, VersionType};
+use crate::games::common::HttpClient;
+use crate::singleflight::SingleFlight;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Remembers loader results for a fixed time. Expired values are kept around so they can
+/// still be served when the upstream fails.
+#[derive(Debug)]
+struct MetadataCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> MetadataCache<K, V> {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get_fresh(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(fetched, _)| fetched.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn get_stale(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key).map(|(_, value)| value.clone())
+    }
+
+    fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, (Instant::now(), value));
+    }
+}
+
+type BuildsKey = (String, Option<String>);
+
+/// What is known about a loader's upstream from refreshing its versions and builds.
+#[derive(Debug, Clone, Default)]
+pub struct LoaderHealth {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Whether a version list, fresh or stale, is available to serve.
+    pub cached: bool,
+}
+
+impl LoaderHealth {
+    /// Whether the most recent refresh failed.
+    pub fn failing(&self) -> bool {
+        match (self.last_failure, self.last_success) {
+            (Some(failure), Some(success)) => failure > success,
+            (failure, _) => failure.is_some(),
+        }
+    }
+
+    /// Records the outcome of a refresh. Errors that a client caused, such as asking for an
+    /// installer the loader does not publish, say nothing about the upstream and are ignored.
+    fn record<T>(&mut self, result: &anyhow::Result<T>) {
+        match result {
+            Ok(_) => self.last_success = Some(Utc::now()),
+            Err(e) if WarehouseError::from(e).status().is_server_error() => {
+                self.last_failure = Some(Utc::now());
+                self.last_error = Some(format!("{:#}", e));
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Sits in front of every registered loader. Results are served from memory while they are
+/// fresh, concurrent refreshes are coalesced into a single upstream call, and the last known
+/// result is served if a refresh fails.
+#[derive(Debug)]
+pub struct CachedLoader {
+    inner: Arc<dyn GameLoader>,
+    versions: Arc<MetadataCache<(), Vec<Version>>>,
+    builds: Arc<MetadataCache<BuildsKey, Vec<Build>>>,
+    versions_flight: SingleFlight<(), Vec<Version>>,
+    builds_flight: SingleFlight<BuildsKey, Vec<Build>>,
+    health: Arc<Mutex<LoaderHealth>>,
+}
+
+impl CachedLoader {
+    pub fn new(inner: Arc<dyn GameLoader>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            versions: Arc::new(MetadataCache::new(ttl)),
+            builds: Arc::new(MetadataCache::new(ttl)),
+            versions_flight: SingleFlight::default(),
+            builds_flight: SingleFlight::default(),
+            health: Arc::new(Mutex::new(LoaderHealth::default())),
+        }
+    }
+
+    /// Points download URLs at the configured mirrors. Builds are cached with their
+    /// upstream URLs, so that they follow changes to the mirrors.
+    fn mirrored(&self, builds: Vec<Build>) -> Vec<Build> {
+        let Some(client) = self.inner.http_client() else {
+            return builds;
+        };
+        let upstream = client.upstream();
+        if upstream.mirrors.is_empty() {
+            return builds;
+        }
+
+        builds
+            .into_iter()
+            .map(|build| match build.download_url() {
+                Some(url) => {
+                    let url = upstream.resolve(url);
+                    build.with_download_url(url)
+                }
+                None => build,
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl GameLoader for CachedLoader {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn website(&self) -> Option<&str> {
+        self.inner.website()
+    }
+
+    fn supports_version_type(&self, version_type: &VersionType) -> bool {
+        self.inner.supports_version_type(version_type)
+    }
+
+    fn http_client(&self) -> Option<&HttpClient> {
+        self.inner.http_client()
+    }
+
+    fn health(&self) -> Option<LoaderHealth> {
+        let mut health = self.health.lock().unwrap().clone();
+        health.cached = self.versions.get_stale(&()).is_some();
+        Some(health)
+    }
+
+    async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+        if let Some(versions) = self.versions.get_fresh(&()) {
+            return Ok(versions);
+        }
+
+        let refresh = {
+            let (inner, cache) = (self.inner.clone(), self.versions.clone());
+            let health = self.health.clone();
+            async move {
+                let fetched = inner.fetch_versions().await;
+                health.lock().unwrap().record(&fetched);
+                if let Ok(versions) = &fetched {
+                    cache.insert((), versions.clone());
+                }
+                fetched
+            }
+        };
+        let fetched = self.versions_flight.run((), refresh).await;
+
+        match fetched {
+            Ok(versions) => Ok(versions),
+            Err(e) => match self.versions.get_stale(&()) {
+                Some(versions) => {
+                    warn!("serving stale {} versions: {:#}", self.name(), e);
+                    Ok(versions)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+        self.fetch_builds_with(version, &BuildOptions::default())
+            .await
+    }
+
+    async fn fetch_builds_with(
+        &self,
+        version: &Version,
+        options: &BuildOptions,
+    ) -> anyhow::Result<Vec<Build>> {
+        let key = (version.id().to_string(), options.installer.clone());
+        if let Some(builds) = self.builds.get_fresh(&key) {
+            return Ok(self.mirrored(builds));
+        }
+
+        let refresh = {
+            let (inner, cache, key) = (self.inner.clone(), self.builds.clone(), key.clone());
+            let (version, options) = (version.clone(), options.clone());
+            let health = self.health.clone();
+            async move {
+                let fetched = inner.fetch_builds_with(&version, &options).await;
+                health.lock().unwrap().record(&fetched);
+                if let Ok(builds) = &fetched {
+                    cache.insert(key, builds.clone());
+                }
+                fetched
+            }
+        };
+        let fetched = self.builds_flight.run(key.clone(), refresh).await;
+
+        match fetched {
+            Ok(builds) => Ok(self.mirrored(builds)),
+            Err(e) => match self.builds.get_stale(&key) {
+                Some(builds) => {
+                    warn!(
+                        "serving stale {} builds for {}: {:#}",
+                        self.name(),
+                        version.id(),
+                        e
+                    );
+                    Ok(self.mirrored(builds))
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// A loader that counts its upstream calls and fails them on demand.
+    #[derive(Debug, Default)]
+    struct StubLoader {
+        calls: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    impl StubLoader {
+        fn respond<T>(&self, value: T) -> anyhow::Result<T> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(WarehouseError::Upstream(format!("call {} failed", call)).into());
+            }
+            Ok(value)
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        fn fail(&self, failing: bool) {
+            self.failing.store(failing, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GameLoader for StubLoader {
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        fn website(&self) -> Option<&str> {
+            None
+        }
+
+        async fn fetch_versions(&self) -> anyhow::Result<Vec<Version>> {
+            let id = format!("1.{}", self.calls());
+            self.respond(vec![Version::new_standard(id, VersionType::Release)])
+        }
+
+        async fn fetch_builds(&self, version: &Version) -> anyhow::Result<Vec<Build>> {
+            let id = self.calls().to_string();
+            self.respond(vec![Build::new_standard(id, version.clone(), None)])
+        }
+    }
+
+    fn cached(ttl: Duration) -> (Arc<StubLoader>, CachedLoader) {
+        let stub = Arc::new(StubLoader::default());
+        (stub.clone(), CachedLoader::new(stub, ttl))
+    }
+
+    fn ids(versions: &[Version]) -> Vec<&str> {
+        versions.iter().map(|v| v.id()).collect()
+    }
+
+    fn version() -> Version {
+        Version::new_standard("1.0".to_string(), VersionType::Release)
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_results_without_calling_upstream() {
+        let (stub, loader) = cached(Duration::from_secs(3600));
+
+        assert_eq!(ids(&loader.fetch_versions().await.unwrap()), ["1.0"]);
+        assert_eq!(ids(&loader.fetch_versions().await.unwrap()), ["1.0"]);
+        assert_eq!(loader.fetch_builds(&version()).await.unwrap()[0].id(), "1");
+        assert_eq!(loader.fetch_builds(&version()).await.unwrap()[0].id(), "1");
+        assert_eq!(stub.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn refreshes_expired_results() {
+        let (stub, loader) = cached(Duration::from_millis(20));
+
+        assert_eq!(ids(&loader.fetch_versions().await.unwrap()), ["1.0"]);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(ids(&loader.fetch_versions().await.unwrap()), ["1.1"]);
+        assert_eq!(stub.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_results_when_upstream_fails() {
+        let (stub, loader) = cached(Duration::ZERO);
+
+        assert_eq!(ids(&loader.fetch_versions().await.unwrap()), ["1.0"]);
+        assert_eq!(loader.fetch_builds(&version()).await.unwrap()[0].id(), "1");
+        stub.fail(true);
+        assert_eq!(ids(&loader.fetch_versions().await.unwrap()), ["1.0"]);
+        assert_eq!(loader.fetch_builds(&version()).await.unwrap()[0].id(), "1");
+        assert_eq!(stub.calls(), 4);
+
+        let health = loader.health().unwrap();
+        assert!(health.failing() && health.cached);
+        assert_eq!(
+            health.last_error.as_deref(),
+            Some("Upstream request failed: call 3 failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_without_a_result_to_fall_back_on() {
+        let (stub, loader) = cached(Duration::ZERO);
+        stub.fail(true);
+
+        assert!(loader.fetch_versions().await.is_err());
+        assert!(!loader.health().unwrap().cached);
+    }
+
+    #[tokio::test]
+    async fn tracks_health_of_build_refreshes() {
+        let (stub, loader) = cached(Duration::ZERO);
+        assert!(!loader.health().unwrap().failing());
+
+        stub.fail(true);
+        assert!(loader.fetch_builds(&version()).await.is_err());
+        let health = loader.health().unwrap();
+        assert!(health.failing());
+        assert_eq!(
+            health.last_error.as_deref(),
+            Some("Upstream request failed: call 0 failed")
+        );
+
+        stub.fail(false);
+        assert!(loader.fetch_builds(&version()).await.is_ok());
+        assert!(!loader.health().unwrap().failing());
+    }
+}
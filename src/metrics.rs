@@ -0,0 +1,185 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Every metric warehouse exports. They are process-wide so that the cache, the loaders'
+/// HTTP clients and the API can record into them without being handed a registry.
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    /// Time until the response head is ready; streamed bodies are not included.
+    pub http_request_duration: HistogramVec,
+    pub cache_hits: IntCounter,
+    pub cache_misses: IntCounter,
+    pub cache_evictions: IntCounterVec,
+    pub cache_entries: IntGauge,
+    pub cache_size: IntGauge,
+    pub bytes_served: IntCounterVec,
+    pub upstream_requests: IntCounterVec,
+    pub upstream_request_duration: HistogramVec,
+    pub upstream_errors: IntCounterVec,
+    pub downloads_in_flight: IntGauge,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("warehouse".to_string()), None)
+            .expect("metric namespace is valid");
+
+        let metrics = Self {
+            http_requests: IntCounterVec::new(
+                Opts::new(
+                    "http_requests_total",
+                    "API requests by route, method and status",
+                ),
+                &["route", "method", "status"],
+            )
+            .unwrap(),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "Time to answer API requests, up to the response head",
+                ),
+                &["route", "method"],
+            )
+            .unwrap(),
+            cache_hits: IntCounter::new("cache_hits_total", "Downloads served from the cache")
+                .unwrap(),
+            cache_misses: IntCounter::new(
+                "cache_misses_total",
+                "Downloads not found in the cache, or found stale or corrupted",
+            )
+            .unwrap(),
+            cache_evictions: IntCounterVec::new(
+                Opts::new(
+                    "cache_evictions_total",
+                    "Cache entries removed because they expired or to stay within the quota",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+            cache_entries: IntGauge::new("cache_entries", "Entries in the cache").unwrap(),
+            cache_size: IntGauge::new("cache_size_bytes", "Total size of the cache entries")
+                .unwrap(),
+            bytes_served: IntCounterVec::new(
+                Opts::new(
+                    "bytes_served_total",
+                    "Artifact bytes sent to clients, from the cache or straight from upstream",
+                ),
+                &["source"],
+            )
+            .unwrap(),
+            upstream_requests: IntCounterVec::new(
+                Opts::new(
+                    "upstream_requests_total",
+                    "Metadata requests to upstream APIs by loader and status",
+                ),
+                &["loader", "status"],
+            )
+            .unwrap(),
+            upstream_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "upstream_request_duration_seconds",
+                    "Time taken by metadata requests to upstream APIs",
+                ),
+                &["loader"],
+            )
+            .unwrap(),
+            upstream_errors: IntCounterVec::new(
+                Opts::new(
+                    "upstream_errors_total",
+                    "Metadata requests to upstream APIs that failed or returned an error status",
+                ),
+                &["loader"],
+            )
+            .unwrap(),
+            downloads_in_flight: IntGauge::new(
+                "downloads_in_flight",
+                "Builds currently being downloaded from upstream into the cache",
+            )
+            .unwrap(),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 12] = [
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_request_duration.clone()),
+            Box::new(metrics.cache_hits.clone()),
+            Box::new(metrics.cache_misses.clone()),
+            Box::new(metrics.cache_evictions.clone()),
+            Box::new(metrics.cache_entries.clone()),
+            Box::new(metrics.cache_size.clone()),
+            Box::new(metrics.bytes_served.clone()),
+            Box::new(metrics.upstream_requests.clone()),
+            Box::new(metrics.upstream_request_duration.clone()),
+            Box::new(metrics.upstream_errors.clone()),
+            Box::new(metrics.downloads_in_flight.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metrics are registered once");
+        }
+
+        metrics
+    }
+
+    pub fn observe_request(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[route, method, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[route, method])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Records an upstream request; `status` is `None` when no response was received.
+    pub fn observe_upstream(&self, loader: &str, status: Option<u16>, elapsed: Duration) {
+        let label = status.map_or_else(|| "error".to_string(), |status| status.to_string());
+        self.upstream_requests
+            .with_label_values(&[loader, &label])
+            .inc();
+        self.upstream_request_duration
+            .with_label_values(&[loader])
+            .observe(elapsed.as_secs_f64());
+        // 304 is how revalidated metadata is confirmed, not a failure.
+        if status.is_none_or(|status| status >= 400) {
+            self.upstream_errors.with_label_values(&[loader]).inc();
+        }
+    }
+
+    /// Renders every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding does not fail");
+        String::from_utf8(buffer).expect("text encoding is UTF-8")
+    }
+}
+
+/// Counts one in-flight download for as long as it is held.
+pub struct InFlight(());
+
+impl InFlight {
+    pub fn start() -> Self {
+        metrics().downloads_in_flight.inc();
+        Self(())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        metrics().downloads_in_flight.dec();
+    }
+}
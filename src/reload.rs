@@ -0,0 +1,90 @@
+use crate::config::Settings;
+use crate::game::GameProvider;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+/// How often the config file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reloads the settings whenever the config file changes or the process receives
+/// `SIGHUP`, and applies them to `games`. Invalid settings are logged and ignored, so the
+/// current ones stay in effect.
+pub async fn watch(games: Arc<GameProvider>, path: Option<PathBuf>) {
+    let hangup = Arc::new(Notify::new());
+    #[cfg(unix)]
+    tokio::spawn(forward_hangups(hangup.clone()));
+
+    let mut modified = path.as_deref().and_then(modified_time);
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = hangup.notified() => info!("received SIGHUP, reloading configuration"),
+            _ = interval.tick() => {
+                let Some(path) = &path else {
+                    continue;
+                };
+                let current = modified_time(path);
+                if current == modified {
+                    continue;
+                }
+                modified = current;
+                info!("{} changed, reloading configuration", path.display());
+            }
+        }
+
+        reload(&games, path.as_deref()).await;
+    }
+}
+
+#[cfg(unix)]
+async fn forward_hangups(hangup: Arc<Notify>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = match signal(SignalKind::hangup()) {
+        Ok(signals) => signals,
+        Err(e) => {
+            error!("cannot listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while signals.recv().await.is_some() {
+        hangup.notify_one();
+    }
+}
+
+/// The file's modification time, or `None` while it cannot be read, such as in the middle
+/// of an editor replacing it.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+async fn reload(games: &GameProvider, path: Option<&Path>) {
+    let settings = match Settings::load(path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("keeping the current configuration, reload failed: {:#}", e);
+            return;
+        }
+    };
+
+    let changes = games.settings().await.diff(&settings);
+    if changes.is_empty() {
+        info!("configuration unchanged");
+        return;
+    }
+
+    if let Err(e) = games.reconfigure(settings).await {
+        error!("keeping the current configuration, reload failed: {:#}", e);
+        return;
+    }
+    for change in changes {
+        if change.needs_restart() {
+            warn!("{} (takes effect after a restart)", change);
+        } else {
+            info!("{}", change);
+        }
+    }
+}
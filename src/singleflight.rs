@@ -0,0 +1,225 @@
+use crate::error::WarehouseError;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+type SharedResult<V> = Result<V, Arc<anyhow::Error>>;
+type Waiter<V> = Shared<BoxFuture<'static, SharedResult<V>>>;
+
+/// Deduplicates concurrent work by key: while a call for a key is in flight, later
+/// callers wait for it and receive a clone of its result instead of doing the work again.
+/// The work runs in a task of its own, so that it completes for the callers waiting on it
+/// even when the caller that started it goes away.
+#[derive(Debug)]
+pub struct SingleFlight<K, V: Clone> {
+    calls: Arc<Mutex<HashMap<K, Waiter<V>>>>,
+}
+
+impl<K, V: Clone> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            calls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+pub enum Flight<K: Eq + Hash, V: Clone> {
+    /// No call was in flight; the caller must do the work and report it through the guard,
+    /// in a task that outlives its own request.
+    Leader(FlightGuard<K, V>),
+    /// Another caller is already doing the work.
+    Follower(Waiter<V>),
+}
+
+impl<K, V> SingleFlight<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + Send + Sync + 'static,
+{
+    pub fn begin(&self, key: K) -> Flight<K, V> {
+        let mut calls = self.calls.lock().unwrap();
+        if let Some(waiter) = calls.get(&key) {
+            return Flight::Follower(waiter.clone());
+        }
+
+        let (tx, rx) = oneshot::channel::<SharedResult<V>>();
+        let waiter = rx
+            .map(|res| {
+                res.unwrap_or_else(|_| {
+                    Err(Arc::new(anyhow::anyhow!("in-flight request was cancelled")))
+                })
+            })
+            .boxed()
+            .shared();
+        calls.insert(key.clone(), waiter.clone());
+
+        Flight::Leader(FlightGuard {
+            calls: self.calls.clone(),
+            key,
+            tx: Some(tx),
+            waiter,
+        })
+    }
+
+    /// Runs `f` in a spawned task unless a call for `key` is already in flight, and waits
+    /// for the result either way.
+    pub async fn run<F>(&self, key: K, f: F) -> anyhow::Result<V>
+    where
+        K: Send + 'static,
+        F: Future<Output = anyhow::Result<V>> + Send + 'static,
+    {
+        match self.begin(key) {
+            Flight::Leader(guard) => {
+                let waiter = guard.waiter();
+                tokio::spawn(async move {
+                    guard.finish(f.await).ok();
+                });
+                wait(waiter).await
+            }
+            Flight::Follower(waiter) => wait(waiter).await,
+        }
+    }
+}
+
+/// Errors are shared between callers as [`WarehouseError`]s, which keep what kind of
+/// failure they were.
+pub async fn wait<V: Clone>(waiter: Waiter<V>) -> anyhow::Result<V> {
+    waiter
+        .await
+        .map_err(|e| WarehouseError::from(e.as_ref()).into())
+}
+
+/// Held by the caller doing the work. Dropping it without finishing fails the followers.
+pub struct FlightGuard<K: Eq + Hash, V: Clone> {
+    calls: Arc<Mutex<HashMap<K, Waiter<V>>>>,
+    key: K,
+    tx: Option<oneshot::Sender<SharedResult<V>>>,
+    waiter: Waiter<V>,
+}
+
+impl<K: Eq + Hash, V: Clone> FlightGuard<K, V> {
+    /// What the leader's own caller waits on, like every follower.
+    pub fn waiter(&self) -> Waiter<V> {
+        self.waiter.clone()
+    }
+
+    /// Hands the result to every follower and returns it to the leader.
+    pub fn finish(mut self, result: anyhow::Result<V>) -> anyhow::Result<V> {
+        self.calls.lock().unwrap().remove(&self.key);
+        let tx = self.tx.take().expect("flight finished twice");
+        match result {
+            Ok(value) => {
+                tx.send(Ok(value.clone())).ok();
+                Ok(value)
+            }
+            Err(e) => {
+                tx.send(Err(Arc::new(WarehouseError::from(&e).into()))).ok();
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> Drop for FlightGuard<K, V> {
+    fn drop(&mut self) {
+        if self.tx.is_some() {
+            self.calls.lock().unwrap().remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::time::{sleep, timeout};
+
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn runs_concurrent_calls_once() {
+        let flight = Arc::new(SingleFlight::<&str, u32>::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let calls = (0..8).map(|_| {
+            let runs = runs.clone();
+            flight.run("key", async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(50)).await;
+                Ok(7)
+            })
+        });
+        for result in join_all(calls).await {
+            assert_eq!(result.unwrap(), 7);
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // Finished calls are forgotten, so the next one does the work again.
+        assert_eq!(flight.run("key", async { Ok(8) }).await.unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn shares_errors_with_every_waiter() {
+        let flight = SingleFlight::<&str, u32>::default();
+
+        let calls = (0..4).map(|_| {
+            flight.run("key", async {
+                sleep(Duration::from_millis(50)).await;
+                Err(WarehouseError::BuildNotFound("gone".to_string()).into())
+            })
+        });
+        for result in join_all(calls).await {
+            let error = result.unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<WarehouseError>(),
+                Some(&WarehouseError::BuildNotFound("gone".to_string()))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn completes_for_followers_when_the_leader_goes_away() {
+        let flight = Arc::new(SingleFlight::<&str, u32>::default());
+
+        let leader = tokio::spawn({
+            let flight = flight.clone();
+            async move {
+                flight
+                    .run("key", async {
+                        sleep(Duration::from_millis(100)).await;
+                        Ok(7)
+                    })
+                    .await
+            }
+        });
+        sleep(Duration::from_millis(20)).await;
+        let Flight::Follower(waiter) = flight.begin("key") else {
+            panic!("the leader's call is not in flight");
+        };
+        leader.abort();
+
+        let result = timeout(PATIENCE, wait(waiter)).await.unwrap();
+        assert_eq!(result.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn fails_followers_of_a_dropped_guard() {
+        let flight = SingleFlight::<&str, u32>::default();
+        let Flight::Leader(guard) = flight.begin("key") else {
+            panic!("no call should be in flight");
+        };
+        let Flight::Follower(waiter) = flight.begin("key") else {
+            panic!("the guard's call is not in flight");
+        };
+        drop(guard);
+
+        let result = timeout(PATIENCE, wait(waiter)).await.unwrap();
+        assert!(result.is_err());
+        assert!(matches!(flight.begin("key"), Flight::Leader(_)));
+    }
+}
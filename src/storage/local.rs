@@ -0,0 +1,142 @@
+use super::{ByteStream, ObjectInfo, StorageBackend};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::stream;
+use std::io::{self, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Keeps objects as files under a directory, one subdirectory per game and loader.
+#[derive(Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn stat(&self, key: &str) -> anyhow::Result<Option<ObjectInfo>> {
+        match fs::metadata(self.path(key)).await {
+            Ok(metadata) => Ok(Some(ObjectInfo {
+                key: key.to_string(),
+                size: metadata.len(),
+                last_modified: metadata.modified()?.into(),
+            })),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> anyhow::Result<Option<ByteStream>> {
+        let mut file = match fs::File::open(self.path(key)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let reader = match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start)).await?;
+                file.take(range.end - range.start)
+            }
+            None => file.take(u64::MAX),
+        };
+
+        Ok(Some(Box::pin(stream::try_unfold(
+            reader,
+            |mut reader| async move {
+                let mut buf = vec![0u8; READ_CHUNK_SIZE];
+                let n = reader.read(&mut buf).await?;
+                if n == 0 {
+                    return Ok(None);
+                }
+                buf.truncate(n);
+                Ok(Some((Bytes::from(buf), reader)))
+            },
+        ))))
+    }
+
+    /// Renames the staged file into place, so it must be on the same filesystem.
+    async fn put(&self, key: &str, staged: &Path) -> anyhow::Result<()> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(staged, &path).await?;
+        sync_parent(&path).await
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match fs::remove_file(self.path(key)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lists every file in the subdirectories of the root. Files directly in the root,
+    /// such as the cache index, are not objects.
+    async fn list(&self) -> anyhow::Result<Vec<ObjectInfo>> {
+        let mut result = Vec::new();
+        let mut dirs = vec![(self.root.clone(), None::<String>)];
+
+        while let Some((dir, prefix)) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let key = match &prefix {
+                    Some(prefix) => format!("{}/{}", prefix, name),
+                    None => name,
+                };
+
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    dirs.push((entry.path(), Some(key)));
+                } else if metadata.is_file() && prefix.is_some() {
+                    result.push(ObjectInfo {
+                        key,
+                        size: metadata.len(),
+                        last_modified: metadata.modified()?.into(),
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        Some(self.path(key))
+    }
+}
+
+/// Persists renames in the object's directory. Windows cannot open directories as files
+/// and makes renames durable on its own, so this is a no-op there.
+#[cfg(unix)]
+async fn sync_parent(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::File::open(parent).await?.sync_all().await?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn sync_parent(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,72 @@
+mod local;
+mod s3;
+
+use crate::config::Settings;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
+use std::fmt::Debug;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+pub type ByteStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// An object held by a [`StorageBackend`].
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// Where cached artifacts are kept. Objects are addressed by `{game}/{filename}` keys;
+/// what they contain and whether they are still valid is tracked by the cache index.
+#[async_trait]
+pub trait StorageBackend: Send + Sync + Debug {
+    /// Returns the metadata of the object at `key`, or `None` if it does not exist.
+    async fn stat(&self, key: &str) -> anyhow::Result<Option<ObjectInfo>>;
+
+    /// Opens the object at `key`, or only `range` of its bytes, as a stream. Returns `None`
+    /// if the object does not exist.
+    async fn get(&self, key: &str, range: Option<Range<u64>>)
+        -> anyhow::Result<Option<ByteStream>>;
+
+    /// Stores the complete file at `staged` under `key`, replacing any existing object.
+    /// The staged file is consumed.
+    async fn put(&self, key: &str, staged: &Path) -> anyhow::Result<()>;
+
+    /// Removes the object at `key`. Removing a missing object is not an error.
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+
+    async fn list(&self) -> anyhow::Result<Vec<ObjectInfo>>;
+
+    /// The file backing `key` when objects live on the local disk, so that they can be
+    /// served without going through [`StorageBackend::get`].
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// A URL that clients can fetch the object from directly for `ttl`, if the backend
+    /// supports handing out such URLs.
+    async fn presign(&self, _key: &str, _ttl: Duration) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+pub fn from_settings(settings: &Settings) -> anyhow::Result<Arc<dyn StorageBackend>> {
+    match settings.storage_backend.as_str() {
+        "local" => Ok(Arc::new(LocalStorage::new(PathBuf::from(
+            &settings.storage_path,
+        )))),
+        "s3" => Ok(Arc::new(S3Storage::from_settings(settings)?)),
+        other => anyhow::bail!("unknown storage backend '{}'", other),
+    }
+}
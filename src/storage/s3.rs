@@ -0,0 +1,182 @@
+use super::{ByteStream, ObjectInfo, StorageBackend};
+use crate::config::Settings;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::signer::Signer;
+use object_store::{GetOptions, GetRange, ObjectMeta, ObjectStore, PutPayload, WriteMultipart};
+use reqwest::Method;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+/// Files up to this size are uploaded in a single request, larger ones in parts.
+const MULTIPART_THRESHOLD: u64 = 8 * 1024 * 1024;
+const MAX_CONCURRENT_PARTS: usize = 4;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Keeps objects in an S3-compatible bucket, optionally below a key prefix.
+///
+/// Settings left unset fall back to the standard `AWS_*` environment variables.
+#[derive(Debug)]
+pub struct S3Storage {
+    store: AmazonS3,
+    prefix: String,
+}
+
+impl S3Storage {
+    pub fn from_settings(settings: &Settings) -> anyhow::Result<Self> {
+        let mut builder = AmazonS3Builder::from_env();
+        if let Some(bucket) = &settings.s3_bucket {
+            builder = builder.with_bucket_name(bucket);
+        }
+        if let Some(region) = &settings.s3_region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &settings.s3_endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(access_key_id) = &settings.s3_access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &settings.s3_secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+
+        let prefix = settings
+            .s3_prefix
+            .as_deref()
+            .unwrap_or_default()
+            .trim_matches('/');
+
+        Ok(Self {
+            store: builder.build()?,
+            prefix: if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", prefix)
+            },
+        })
+    }
+
+    fn path(&self, key: &str) -> ObjectPath {
+        ObjectPath::from(format!("{}{}", self.prefix, key))
+    }
+
+    fn info(&self, meta: ObjectMeta) -> Option<ObjectInfo> {
+        Some(ObjectInfo {
+            key: meta
+                .location
+                .as_ref()
+                .strip_prefix(&self.prefix)?
+                .to_string(),
+            size: meta.size as u64,
+            last_modified: meta.last_modified,
+        })
+    }
+
+    async fn put_multipart(&self, path: &ObjectPath, mut file: fs::File) -> anyhow::Result<()> {
+        let mut upload = WriteMultipart::new(self.store.put_multipart(path).await?);
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+
+        loop {
+            let read = async {
+                upload.wait_for_capacity(MAX_CONCURRENT_PARTS).await?;
+                anyhow::Ok(file.read(&mut buf).await?)
+            };
+            match read.await {
+                Ok(0) => break,
+                Ok(n) => upload.write(&buf[..n]),
+                Err(e) => {
+                    upload.abort().await.ok();
+                    return Err(e);
+                }
+            }
+        }
+
+        upload.finish().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Storage {
+    async fn stat(&self, key: &str) -> anyhow::Result<Option<ObjectInfo>> {
+        match self.store.head(&self.path(key)).await {
+            Ok(meta) => Ok(self.info(meta)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> anyhow::Result<Option<ByteStream>> {
+        let options = GetOptions {
+            range: range.map(|r| GetRange::Bounded(r.start as usize..r.end as usize)),
+            ..Default::default()
+        };
+
+        match self.store.get_opts(&self.path(key), options).await {
+            Ok(result) => Ok(Some(Box::pin(
+                result
+                    .into_stream()
+                    .map(|chunk| chunk.map_err(io::Error::other)),
+            ))),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn put(&self, key: &str, staged: &Path) -> anyhow::Result<()> {
+        let path = self.path(key);
+        let file = fs::File::open(staged).await?;
+
+        if file.metadata().await?.len() <= MULTIPART_THRESHOLD {
+            let data = fs::read(staged).await?;
+            self.store.put(&path, PutPayload::from(data)).await?;
+        } else {
+            self.put_multipart(&path, file).await?;
+        }
+
+        fs::remove_file(staged).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match self.store.delete(&self.path(key)).await {
+            Err(object_store::Error::NotFound { .. }) | Ok(()) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self) -> anyhow::Result<Vec<ObjectInfo>> {
+        let prefix = ObjectPath::from(self.prefix.as_str());
+        let mut objects = self
+            .store
+            .list((!self.prefix.is_empty()).then_some(&prefix));
+        let mut result = Vec::new();
+
+        while let Some(meta) = objects.next().await {
+            result.extend(self.info(meta?));
+        }
+
+        Ok(result)
+    }
+
+    async fn presign(&self, key: &str, ttl: Duration) -> anyhow::Result<Option<String>> {
+        let url = self
+            .store
+            .signed_url(Method::GET, &self.path(key), ttl)
+            .await?;
+        Ok(Some(url.to_string()))
+    }
+}
@@ -0,0 +1,430 @@
+use crate::game::{natural_cmp, Version};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// The last weekly snapshot before each release, oldest first. Snapshot IDs only carry a
+/// year and week, so this is what places them among releases; a snapshot targets the first
+/// release whose last snapshot is not older than it. Snapshots newer than the last entry
+/// target a release that is not listed yet and sort after every listed one.
+const SNAPSHOT_TARGETS: &[(u32, u32, &str)] = &[
+    (12, 1, "1.1"),
+    (12, 8, "1.2.1"),
+    (12, 30, "1.3.1"),
+    (12, 42, "1.4.2"),
+    (12, 50, "1.4.6"),
+    (13, 10, "1.5"),
+    (13, 12, "1.5.1"),
+    (13, 26, "1.6"),
+    (13, 43, "1.7.2"),
+    (13, 49, "1.7.4"),
+    (14, 34, "1.8"),
+    (16, 7, "1.9"),
+    (16, 15, "1.9.3"),
+    (16, 21, "1.10"),
+    (16, 44, "1.11"),
+    (16, 50, "1.11.1"),
+    (17, 18, "1.12"),
+    (17, 31, "1.12.1"),
+    (18, 22, "1.13"),
+    (18, 33, "1.13.1"),
+    (19, 14, "1.14"),
+    (19, 52, "1.15"),
+    (20, 22, "1.16"),
+    (20, 30, "1.16.2"),
+    (21, 20, "1.17"),
+    (21, 44, "1.18"),
+    (22, 7, "1.18.2"),
+    (22, 19, "1.19"),
+    (22, 24, "1.19.1"),
+    (22, 46, "1.19.3"),
+    (23, 7, "1.19.4"),
+    (23, 18, "1.20"),
+    (23, 35, "1.20.2"),
+    (23, 46, "1.20.3"),
+    (24, 14, "1.20.5"),
+    (24, 21, "1.21"),
+    (24, 40, "1.21.2"),
+    (24, 46, "1.21.4"),
+    (25, 10, "1.21.5"),
+    (25, 21, "1.21.6"),
+    (25, 37, "1.21.9"),
+];
+
+/// Pre-Beta development phases, oldest first, by the prefix of their version IDs.
+const LEGACY_PHASES: &[&str] = &["rd-", "c", "inf-", "a", "b"];
+
+/// Sorts version IDs in release order, understanding Minecraft's formats: releases such as
+/// `1.20.4`, weekly snapshots such as `23w14a`, pre-releases (`1.20.5-pre1`, and the older
+/// `1.14 Pre-Release 1`), release candidates (`1.20.5-rc1`) and the Alpha and Beta era.
+/// Snapshots, pre-releases and release candidates sort before the release they lead up
+/// to, in that order. IDs in none of these formats sort before everything else.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VersionKey {
+    release: Release,
+    stage: Stage,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Release {
+    Unknown(String),
+    Legacy(usize, Vec<u64>),
+    Numbered(Vec<u64>),
+    /// The target of snapshots newer than [`SNAPSHOT_TARGETS`].
+    Unlisted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    /// Below every other stage of a release; only used for range bounds.
+    Start,
+    Snapshot(u32, u32, String),
+    Other(Suffix),
+    PreRelease(u64),
+    ReleaseCandidate(u64),
+    Final,
+}
+
+/// A suffix in none of the known formats, such as `-beta10`. Runs of digits compare as
+/// numbers, as in build IDs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Suffix(String);
+
+impl Ord for Suffix {
+    fn cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(&self.0, &other.0).then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for Suffix {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl VersionKey {
+    pub fn parse(id: &str) -> Self {
+        parse_snapshot(id)
+            .or_else(|| parse_release(id))
+            .or_else(|| parse_legacy(id))
+            .unwrap_or_else(|| VersionKey {
+                release: Release::Unknown(id.to_string()),
+                stage: Stage::Final,
+            })
+    }
+
+    /// The lowest key of the same release, below its snapshots and pre-releases.
+    fn start(&self) -> Self {
+        VersionKey {
+            release: self.release.clone(),
+            stage: Stage::Start,
+        }
+    }
+
+    /// The release numbers of a release, pre-release or release candidate.
+    fn numbers(&self) -> Option<&[u64]> {
+        match (&self.release, &self.stage) {
+            (_, Stage::Snapshot(..)) => None,
+            (Release::Numbered(numbers), _) => Some(numbers),
+            _ => None,
+        }
+    }
+}
+
+impl Version {
+    /// The key this version sorts by. See [`VersionKey`].
+    pub fn sort_key(&self) -> VersionKey {
+        VersionKey::parse(self.id())
+    }
+}
+
+fn parse_snapshot(id: &str) -> Option<VersionKey> {
+    let (year, rest) = id.split_at_checked(2)?;
+    let rest = rest.strip_prefix('w')?;
+    let (week, suffix) = rest.split_at_checked(2)?;
+    if !year.bytes().all(|b| b.is_ascii_digit())
+        || !week.bytes().all(|b| b.is_ascii_digit())
+        || suffix.is_empty()
+    {
+        return None;
+    }
+    let (year, week) = (year.parse().ok()?, week.parse().ok()?);
+
+    let release = SNAPSHOT_TARGETS
+        .iter()
+        .find(|(last_year, last_week, _)| (year, week) <= (*last_year, *last_week))
+        .map_or(Release::Unlisted, |(_, _, release)| {
+            Release::Numbered(parse_numbers(release).unwrap_or_default())
+        });
+
+    Some(VersionKey {
+        release,
+        stage: Stage::Snapshot(year, week, suffix.to_string()),
+    })
+}
+
+fn parse_release(id: &str) -> Option<VersionKey> {
+    let end = id
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(id.len());
+    let (numbers, suffix) = id.split_at(end);
+    let numbers = parse_numbers(numbers)?;
+
+    let stage = if suffix.is_empty() {
+        Stage::Final
+    } else if let Some(n) = strip_numbered(suffix, &["-pre", " Pre-Release ", " Pre-release "]) {
+        Stage::PreRelease(n)
+    } else if let Some(n) = strip_numbered(suffix, &["-rc", " Release Candidate "]) {
+        Stage::ReleaseCandidate(n)
+    } else if suffix.starts_with(['-', ' ', '_']) {
+        Stage::Other(Suffix(suffix.to_string()))
+    } else {
+        return None;
+    };
+
+    Some(VersionKey {
+        release: Release::Numbered(numbers),
+        stage,
+    })
+}
+
+fn parse_legacy(id: &str) -> Option<VersionKey> {
+    let (phase, rest) = LEGACY_PHASES
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(phase, prefix)| Some((phase, id.strip_prefix(prefix)?)))?;
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let numbers = rest
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().unwrap_or(u64::MAX))
+        .collect();
+
+    Some(VersionKey {
+        release: Release::Legacy(phase, numbers),
+        stage: Stage::Final,
+    })
+}
+
+/// Parses dot-separated numbers, dropping trailing zeros so that `1.20` and `1.20.0` are
+/// the same release.
+fn parse_numbers(s: &str) -> Option<Vec<u64>> {
+    let mut numbers = s
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    while numbers.len() > 1 && numbers.last() == Some(&0) {
+        numbers.pop();
+    }
+    Some(numbers)
+}
+
+fn strip_numbered(suffix: &str, prefixes: &[&str]) -> Option<u64> {
+    prefixes
+        .iter()
+        .find_map(|prefix| suffix.strip_prefix(prefix)?.parse().ok())
+}
+
+/// A set of constraints on versions, all of which must hold, such as `>=1.20 <1.21`.
+///
+/// Constraints are separated by spaces or commas and take one of the forms `>=V`, `>V`,
+/// `<=V`, `<V`, `=V` or a bare `V` (exactly `V`), `1.20.x` (`1.20` up to `1.21`) and `~1.19.2`
+/// (`1.19.2` up to the next minor release). Upper bounds exclude the snapshots and
+/// pre-releases of the release they name, so `<1.21` stops at the last `1.20.x`.
+#[derive(Clone, Debug)]
+pub struct VersionRange {
+    constraints: Vec<(Ordering, bool, VersionKey)>,
+}
+
+impl VersionRange {
+    pub fn matches(&self, version: &Version) -> bool {
+        let key = version.sort_key();
+        self.constraints
+            .iter()
+            .all(|(ordering, inclusive, bound)| match key.cmp(bound) {
+                Ordering::Equal => *inclusive,
+                actual => actual == *ordering,
+            })
+    }
+
+    fn push(&mut self, ordering: Ordering, inclusive: bool, bound: VersionKey) {
+        self.constraints.push((ordering, inclusive, bound));
+    }
+
+    /// Adds `[lower, upper)`, where `upper` is `lower`'s release numbers cut to `keep`
+    /// components with the last one incremented.
+    fn push_span(
+        &mut self,
+        lower: VersionKey,
+        keep: usize,
+        constraint: &str,
+    ) -> anyhow::Result<()> {
+        let mut upper = lower
+            .numbers()
+            .ok_or_else(|| anyhow::anyhow!("'{}' needs a release version", constraint))?
+            .to_vec();
+        upper.resize(keep, 0);
+        upper[keep - 1] += 1;
+
+        let upper = VersionKey {
+            release: Release::Numbered(upper),
+            stage: Stage::Start,
+        };
+        self.push(Ordering::Greater, true, lower);
+        self.push(Ordering::Less, false, upper);
+        Ok(())
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut range = VersionRange {
+            constraints: Vec::new(),
+        };
+
+        for constraint in s.split([' ', ',']).filter(|c| !c.is_empty()) {
+            if let Some(version) = constraint.strip_prefix('~') {
+                let keep = version.split('.').count().min(2);
+                range.push_span(VersionKey::parse(version), keep, constraint)?;
+            } else if let Some(version) = constraint
+                .strip_suffix(".x")
+                .or_else(|| constraint.strip_suffix(".*"))
+            {
+                let keep = version.split('.').count();
+                range.push_span(VersionKey::parse(version), keep, constraint)?;
+            } else if let Some(version) = constraint.strip_prefix(">=") {
+                range.push(Ordering::Greater, true, VersionKey::parse(version));
+            } else if let Some(version) = constraint.strip_prefix("<=") {
+                range.push(Ordering::Less, true, VersionKey::parse(version));
+            } else if let Some(version) = constraint.strip_prefix('>') {
+                range.push(Ordering::Greater, false, VersionKey::parse(version));
+            } else if let Some(version) = constraint.strip_prefix('<') {
+                range.push(Ordering::Less, false, VersionKey::parse(version).start());
+            } else {
+                let version = constraint.strip_prefix('=').unwrap_or(constraint);
+                range.push(Ordering::Equal, true, VersionKey::parse(version));
+            }
+        }
+
+        if range.constraints.is_empty() {
+            anyhow::bail!("empty version range");
+        }
+        Ok(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::VersionType;
+
+    fn version(id: &str) -> Version {
+        Version::new_standard(id.to_string(), VersionType::from_id(id))
+    }
+
+    fn matches(range: &str, id: &str) -> bool {
+        range.parse::<VersionRange>().unwrap().matches(&version(id))
+    }
+
+    fn assert_ascending(ids: &[&str]) {
+        for pair in ids.windows(2) {
+            assert!(
+                VersionKey::parse(pair[0]) < VersionKey::parse(pair[1]),
+                "expected {} < {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn orders_stages_before_their_release() {
+        assert_ascending(&[
+            "1.19.4",
+            "23w14a",
+            "1.20",
+            "1.20.4",
+            "24w14a",
+            "1.20.5-pre1",
+            "1.20.5-pre2",
+            "1.20.5-rc1",
+            "1.20.5",
+            "1.20.6",
+            "1.21",
+        ]);
+        assert_ascending(&["19w14b", "1.14 Pre-Release 1", "1.14 Pre-Release 5", "1.14"]);
+        assert_ascending(&["1.0-pre2", "1.0-pre10", "1.0-rc1", "1.0"]);
+    }
+
+    #[test]
+    fn orders_unknown_suffixes_by_their_numbers() {
+        assert_ascending(&[
+            "1.0-beta2",
+            "1.0-beta10",
+            "1.0-beta10a",
+            "1.0-beta11",
+            "1.0-pre1",
+        ]);
+        assert_ascending(&["1.16.5-snapshot9", "1.16.5-snapshot10", "1.16.5"]);
+    }
+
+    #[test]
+    fn orders_legacy_and_unknown_versions() {
+        assert_ascending(&[
+            "something",
+            "rd-132211",
+            "c0.0.11a",
+            "inf-20100618",
+            "a1.0.4",
+            "b1.7.3",
+            "1.0",
+            "1.10",
+        ]);
+        assert_ascending(&["1.21.9", "30w01a"]);
+        assert_eq!(VersionKey::parse("1.20"), VersionKey::parse("1.20.0"));
+    }
+
+    #[test]
+    fn matches_explicit_bounds() {
+        assert!(matches(">=1.20 <1.21", "1.20"));
+        assert!(matches(">=1.20 <1.21", "1.20.6"));
+        assert!(!matches(">=1.20 <1.21", "1.19.4"));
+        assert!(!matches(">=1.20 <1.21", "1.21-pre1"));
+        assert!(matches(">=1.20 <1.21", "24w14a"));
+        assert!(!matches(">=1.20 <1.21", "24w18a"));
+        assert!(!matches(">=1.20 <1.21", "1.21"));
+        assert!(matches(">1.20,<=1.20.2", "1.20.2"));
+        assert!(!matches(">1.20,<=1.20.2", "1.20"));
+        assert!(matches("=1.20.1", "1.20.1"));
+        assert!(!matches("1.20.1", "1.20.2"));
+    }
+
+    #[test]
+    fn matches_wildcards_and_tildes() {
+        assert!(matches("1.20.x", "1.20"));
+        assert!(matches("1.20.*", "1.20.6"));
+        assert!(!matches("1.20.x", "1.21"));
+        assert!(!matches("1.20.x", "1.19.4"));
+
+        assert!(matches("~1.19.2", "1.19.2"));
+        assert!(matches("~1.19.2", "1.19.4"));
+        assert!(!matches("~1.19.2", "1.19.1"));
+        assert!(!matches("~1.19.2", "1.20"));
+    }
+
+    #[test]
+    fn rejects_malformed_ranges() {
+        for range in ["", " , ", "~", "~23w14a", ".x", "snapshot.x"] {
+            assert!(
+                range.parse::<VersionRange>().is_err(),
+                "accepted {:?}",
+                range
+            );
+        }
+    }
+}